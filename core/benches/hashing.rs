@@ -0,0 +1,70 @@
+//! Compares the speed of hashing a large file with [`trees::sha1_sum`] to that of reading it
+//! through `io::copy`.
+//!
+//! Run with `cargo bench -p neocities-deploy-core --features mmap` to measure memory-mapped
+//! hashing (without the feature, both ways are the same). The size of the file, in MiB, is taken
+//! from `HASHING_BENCH_MIB` (256 by default); use a few GiB to measure multi-GB asset folders.
+
+use neocities_deploy_core::trees;
+use sha1::{Digest, Sha1};
+use std::hint::black_box;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use std::{env, fs, io};
+
+/// Number of times each way of hashing is timed, keeping the fastest run.
+const RUNS: usize = 5;
+
+fn main() {
+    let mib: usize = env::var("HASHING_BENCH_MIB")
+        .ok()
+        .and_then(|mib| mib.parse().ok())
+        .unwrap_or(256);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("asset.bin");
+    let chunk: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let mut file = io::BufWriter::new(fs::File::create(&path).unwrap());
+    for _ in 0..mib {
+        io::Write::write_all(&mut file, &chunk).unwrap();
+    }
+    drop(file);
+
+    let features = if cfg!(feature = "mmap") {
+        "mmap"
+    } else {
+        "none"
+    };
+    println!("Hashing a {} MiB file (features: {})", mib, features);
+    let baseline = bench("io::copy", mib, || copy_sha1_sum(&path));
+    let current = bench("trees::sha1_sum", mib, || trees::sha1_sum(&path).unwrap());
+    println!(
+        "trees::sha1_sum is {:.2}x as fast",
+        baseline.as_secs_f64() / current.as_secs_f64()
+    );
+}
+
+/// Time a way of hashing the file, printing and returning its fastest run.
+fn bench(name: &str, mib: usize, hash: impl Fn() -> String) -> Duration {
+    let fastest = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(hash());
+            start.elapsed()
+        })
+        .min()
+        .unwrap();
+    println!(
+        "{:>16}: {:>8.1} ms, {:>7.1} MiB/s",
+        name,
+        fastest.as_secs_f64() * 1000.0,
+        mib as f64 / fastest.as_secs_f64()
+    );
+    fastest
+}
+
+/// Hash a file by reading it, as [`trees::sha1_sum`] does without the `mmap` feature.
+fn copy_sha1_sum(path: &Path) -> String {
+    let mut hasher = Sha1::new();
+    io::copy(&mut fs::File::open(path).unwrap(), &mut hasher).unwrap();
+    format!("{:x}", hasher.finalize())
+}
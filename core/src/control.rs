@@ -0,0 +1,144 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Pausing, resuming and cancelling a running deploy.
+
+use std::sync::{Arc, Condvar, Mutex};
+
+/// State of a running deploy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Running,
+    Paused,
+    Cancelled,
+}
+
+/// Handle used to pause, resume or cancel a running deploy.
+///
+/// Cloning the handle yields another handle to the same deploy, so one clone can be handed to
+/// the executor while another is driven by user input (a signal handler, a TUI, *&c*). The
+/// executor only looks at the state between requests, so an in-flight request is always allowed
+/// to finish.
+#[derive(Clone, Debug)]
+pub struct Control {
+    state: Arc<(Mutex<State>, Condvar)>,
+}
+
+impl Default for Control {
+    fn default() -> Self {
+        Self {
+            state: Arc::new((Mutex::new(State::Running), Condvar::new())),
+        }
+    }
+}
+
+impl Control {
+    /// Create a new handle for a running deploy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pause the deploy after the current request.
+    pub fn pause(&self) {
+        self.transition(State::Running, State::Paused);
+    }
+
+    /// Resume a paused deploy.
+    pub fn resume(&self) {
+        self.transition(State::Paused, State::Running);
+    }
+
+    /// Cancel the deploy after the current request. Cancellation is final.
+    pub fn cancel(&self) {
+        let (lock, cvar) = &*self.state;
+        *lock.lock().unwrap() = State::Cancelled;
+        cvar.notify_all();
+    }
+
+    /// Whether the deploy is paused.
+    pub fn is_paused(&self) -> bool {
+        *self.state.0.lock().unwrap() == State::Paused
+    }
+
+    /// Pause the deploy if it is running, or resume it if it is paused.
+    pub fn toggle_pause(&self) {
+        if !self.transition(State::Running, State::Paused) {
+            self.resume();
+        }
+    }
+
+    /// Whether the deploy has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        *self.state.0.lock().unwrap() == State::Cancelled
+    }
+
+    /// Wait while the deploy is paused, then return whether it may go on.
+    pub fn proceed(&self) -> bool {
+        let (lock, cvar) = &*self.state;
+        let state = cvar
+            .wait_while(lock.lock().unwrap(), |state| *state == State::Paused)
+            .unwrap();
+        *state == State::Running
+    }
+
+    /// Change the state from `from` to `to`, if the current state is `from`, and return whether
+    /// it was changed.
+    fn transition(&self, from: State, to: State) -> bool {
+        let (lock, cvar) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        if *state != from {
+            return false;
+        }
+        *state = to;
+        cvar.notify_all();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{thread, time::Duration};
+
+    #[test]
+    fn test_pause_resume_cancel() {
+        let control = Control::new();
+        assert!(control.proceed());
+
+        control.pause();
+        let waiter = {
+            let control = control.clone();
+            thread::spawn(move || control.proceed())
+        };
+        thread::sleep(Duration::from_millis(50));
+        assert!(!waiter.is_finished());
+        control.resume();
+        assert!(waiter.join().unwrap());
+
+        control.toggle_pause();
+        assert!(control.is_paused());
+        control.toggle_pause();
+        assert!(!control.is_paused());
+
+        control.pause();
+        control.cancel();
+        control.resume();
+        assert!(control.is_cancelled());
+        assert!(!control.proceed());
+    }
+}
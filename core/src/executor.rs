@@ -0,0 +1,719 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Running the actions of a deploy against a site.
+
+use crate::control::Control;
+use crate::plan::Action;
+use anyhow::Result;
+use neocities_client::Client;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::Span;
+
+/// What to do when an action fails.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ErrorPolicy {
+    /// Stop the deploy and return the error.
+    #[default]
+    Abort,
+    /// Record the failure and carry on with the other actions.
+    Continue,
+    /// Retry the action, up to `max_retries` times but at least [`POLICY_RETRIES`] times, and
+    /// stop the deploy if it still fails.
+    Retry,
+}
+
+/// Number of times an action is retried under [`ErrorPolicy::Retry`], when `max_retries` is lower.
+pub const POLICY_RETRIES: u32 = 3;
+
+/// Settings for running the actions of a deploy.
+#[derive(Clone, Debug)]
+pub struct ExecuteOptions {
+    /// Maximum number of uploads to run concurrently.
+    pub jobs: usize,
+    /// Maximum number of files in a single upload request.
+    pub batch_files: usize,
+    /// Maximum total size of the files in a single upload request.
+    pub batch_bytes: u64,
+    /// Maximum total size of the files held in memory at once by the uploads running
+    /// concurrently, if any. Batches are kept under it too, so that only a single file larger than
+    /// that can exceed it: such a file is sent alone, once no other upload is running.
+    pub max_memory: Option<u64>,
+    /// Number of times to retry a failed request.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled after each retry.
+    pub retry_delay: Duration,
+    /// What to do when an action fails.
+    pub on_error: ErrorPolicy,
+    /// What to do when an upload fails, instead of `on_error`.
+    pub on_upload_error: Option<ErrorPolicy>,
+    /// What to do when a deletion fails, instead of `on_error`.
+    pub on_delete_error: Option<ErrorPolicy>,
+    /// Go through the actions, reporting their progress, without sending any request.
+    pub dry_run: bool,
+}
+
+impl Default for ExecuteOptions {
+    fn default() -> Self {
+        Self {
+            jobs: 1,
+            batch_files: 20,
+            batch_bytes: 8_000_000,
+            max_memory: None,
+            max_retries: 0,
+            retry_delay: Duration::from_secs(1),
+            on_error: ErrorPolicy::Abort,
+            on_upload_error: None,
+            on_delete_error: None,
+            dry_run: false,
+        }
+    }
+}
+
+impl ExecuteOptions {
+    /// What to do when an action fails, according to its kind.
+    pub fn policy(&self, action: &Action) -> ErrorPolicy {
+        let policy = match action {
+            Action::Upload(_) => self.on_upload_error,
+            Action::DeleteRemote(_) => self.on_delete_error,
+        };
+        policy.unwrap_or(self.on_error)
+    }
+}
+
+/// Something that happened to an action.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The action is going to be run.
+    Planned,
+    /// The request with the action was sent.
+    Started,
+    /// The action succeeded.
+    Completed {
+        /// How long the request took.
+        duration: Duration,
+    },
+    /// The action failed, after all retries.
+    Failed {
+        /// How long the last attempt took.
+        duration: Duration,
+        /// Why the action failed.
+        error: String,
+    },
+    /// The action was not run, because the deploy was cancelled or stopped by an error.
+    Skipped,
+}
+
+impl Event {
+    /// Short machine-readable name of the event.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Planned => "planned",
+            Event::Started => "started",
+            Event::Completed { .. } => "completed",
+            Event::Failed { .. } => "failed",
+            Event::Skipped => "skipped",
+        }
+    }
+}
+
+/// Receives the progress of a deploy. Calls may come from several threads at once.
+pub trait Progress: Sync {
+    /// Called when something happens to an action.
+    fn event(&self, _action: &Action, _event: &Event) {}
+
+    /// Called when a batch of actions has completed, after the events of its actions.
+    fn completed(&self, _batch: &[Action]) {}
+}
+
+/// Ignores the progress of a deploy.
+impl Progress for () {}
+
+/// What came out of running the actions of a deploy.
+#[derive(Debug, Default)]
+pub struct Outcome {
+    /// Number of actions that were processed; less than the number of actions if the deploy was
+    /// cancelled.
+    pub done: usize,
+    /// Actions that failed, with their errors, when the error policy is to continue.
+    pub failures: Vec<(Action, String)>,
+}
+
+impl Outcome {
+    /// Count the actions of the deploy by their result.
+    pub fn summary(&self, actions: &[Action]) -> Summary {
+        let mut summary = Summary {
+            failed: self.failures.len(),
+            ..Summary::default()
+        };
+        let failed = |action: &Action| self.failures.iter().any(|(f, _)| f == action);
+        for action in actions[..self.done].iter().filter(|a| !failed(a)) {
+            match action {
+                Action::Upload(_) => {
+                    summary.uploaded += 1;
+                    summary.bytes += action.bytes();
+                }
+                Action::DeleteRemote(_) => summary.deleted += 1,
+            }
+        }
+        summary
+    }
+}
+
+/// Counts of the actions of a finished deploy.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Summary {
+    /// Number of files uploaded.
+    pub uploaded: usize,
+    /// Number of bytes uploaded.
+    pub bytes: u64,
+    /// Number of remote entries deleted.
+    pub deleted: usize,
+    /// Number of actions that failed.
+    pub failed: usize,
+}
+
+/// Bytes that the uploads running concurrently may hold in memory, shared by the threads of an
+/// [`Executor`].
+#[derive(Debug, Default)]
+struct MemoryBudget {
+    /// Maximum number of bytes in use, if any.
+    limit: Option<u64>,
+    /// Number of bytes in use.
+    used: Mutex<u64>,
+    /// Notified whenever bytes are released.
+    released: Condvar,
+}
+
+/// Bytes taken from a [`MemoryBudget`], released when dropped.
+struct MemoryGuard<'a> {
+    budget: &'a MemoryBudget,
+    bytes: u64,
+}
+
+impl MemoryBudget {
+    fn new(limit: Option<u64>) -> Self {
+        Self {
+            limit,
+            ..Self::default()
+        }
+    }
+
+    /// Take some bytes from the budget, waiting for other uploads to release theirs if there are
+    /// not enough left. More bytes than the limit are granted once nothing else is in use.
+    fn acquire(&self, bytes: u64) -> MemoryGuard<'_> {
+        let mut used = self.used.lock().unwrap();
+        if let Some(limit) = self.limit {
+            let full = |used: &mut u64| *used > 0 && *used + bytes > limit;
+            used = self.released.wait_while(used, full).unwrap();
+        }
+        *used += bytes;
+        MemoryGuard {
+            budget: self,
+            bytes,
+        }
+    }
+
+    fn used(&self) -> MutexGuard<'_, u64> {
+        self.used.lock().unwrap()
+    }
+}
+
+impl Drop for MemoryGuard<'_> {
+    fn drop(&mut self) {
+        *self.budget.used() -= self.bytes;
+        self.budget.released.notify_all();
+    }
+}
+
+/// Executes the actions of a deploy.
+pub struct Executor<'a> {
+    client: &'a Client,
+    options: ExecuteOptions,
+    control: Control,
+    progress: &'a dyn Progress,
+    failures: Mutex<Vec<(Action, String)>>,
+    memory: MemoryBudget,
+}
+
+impl<'a> Executor<'a> {
+    /// Create an executor for a site.
+    pub fn new(client: &'a Client, options: ExecuteOptions) -> Self {
+        Self {
+            client,
+            memory: MemoryBudget::new(options.max_memory),
+            options,
+            control: Control::new(),
+            progress: &(),
+            failures: Mutex::default(),
+        }
+    }
+
+    /// Use a [`Control`] handle, through which the deploy may be paused or cancelled.
+    pub fn control(mut self, control: Control) -> Self {
+        self.control = control;
+        self
+    }
+
+    /// Report the progress of the deploy.
+    pub fn progress(mut self, progress: &'a dyn Progress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Apply the actions in order, checking the [`Control`] handle between them.
+    pub fn execute(self, actions: &[Action]) -> Result<Outcome> {
+        for action in actions {
+            self.progress.event(action, &Event::Planned);
+        }
+        let mut done = 0;
+        let mut outcome = Ok(());
+        while done < actions.len() {
+            // Consecutive uploads run concurrently, but deletions run one at a time, so that a
+            // deletion never overtakes or falls behind an upload of the same path.
+            // HTML uploads get a run of their own, so that they start only after the uploads before
+            // them are finished.
+            let run = match &actions[done] {
+                first @ Action::Upload(_) => actions[done..]
+                    .iter()
+                    .take_while(|action| {
+                        matches!(action, Action::Upload(_)) && action.is_html() == first.is_html()
+                    })
+                    .count(),
+                Action::DeleteRemote(_) => 1,
+            };
+            let (finished, result) = self.execute_run(&actions[done..done + run]);
+            done += finished;
+            if result.is_err() || finished < run {
+                outcome = result;
+                break;
+            }
+        }
+        // The actions never started, after a cancellation or an error, are skipped.
+        for action in &actions[done..] {
+            self.progress.event(action, &Event::Skipped);
+        }
+        outcome?;
+        Ok(Outcome {
+            done,
+            failures: self.failures.into_inner().unwrap(),
+        })
+    }
+
+    /// Apply a run of actions using up to `jobs` threads, in batches.
+    ///
+    /// Batches are started in order, so the actions that were processed always form a prefix of
+    /// the run; its length is returned, along with the error that stopped the run, if any. The threads are given the name of the calling thread, so
+    /// that their log messages can be told apart when several executors run at once, and they run
+    /// within the span of the calling thread.
+    fn execute_run(&self, actions: &[Action]) -> (usize, Result<()>) {
+        let batches = self.batches(actions);
+        let next = AtomicUsize::new(0);
+        let error = Mutex::new(None);
+        let name = thread::current().name().map(str::to_owned);
+        let span = Span::current();
+        thread::scope(|scope| {
+            for _ in 0..self.options.jobs.max(1).min(batches.len()) {
+                let mut builder = thread::Builder::new();
+                if let Some(name) = &name {
+                    builder = builder.name(name.clone());
+                }
+                builder
+                    .spawn_scoped(scope, || {
+                        let _span = span.enter();
+                        while error.lock().unwrap().is_none() && self.control.proceed() {
+                            let Some(batch) = batches.get(next.fetch_add(1, Ordering::SeqCst))
+                            else {
+                                break;
+                            };
+                            if let Err(e) = self.execute_batch(batch) {
+                                error.lock().unwrap().get_or_insert(e);
+                            }
+                        }
+                    })
+                    .expect("failed to spawn thread");
+            }
+        });
+        let started = next.into_inner().min(batches.len());
+        let processed = batches[..started].iter().map(|batch| batch.len()).sum();
+        match error.into_inner().unwrap() {
+            Some(e) => (processed, Err(e)),
+            None => (processed, Ok(())),
+        }
+    }
+
+    /// Split a run of actions into batches that respect the limits on the number of files and
+    /// bytes per request, and the memory budget. A file larger than these limits gets a batch of
+    /// its own.
+    fn batches<'b>(&self, actions: &'b [Action]) -> Vec<&'b [Action]> {
+        let batch_files = self.options.batch_files.max(1);
+        let batch_bytes = match self.options.max_memory {
+            Some(max_memory) => self.options.batch_bytes.min(max_memory),
+            None => self.options.batch_bytes,
+        };
+        let mut batches = Vec::new();
+        let (mut start, mut bytes) = (0, 0);
+        for (i, action) in actions.iter().enumerate() {
+            let full = i - start >= batch_files || bytes + action.bytes() > batch_bytes;
+            if i > start && full {
+                batches.push(&actions[start..i]);
+                (start, bytes) = (i, 0);
+            }
+            bytes += action.bytes();
+        }
+        if start < actions.len() {
+            batches.push(&actions[start..]);
+        }
+        batches
+    }
+
+    /// Apply a batch of actions in a single request, reporting their progress.
+    ///
+    /// Each action gets a span, holding its kind, path and size, that lasts until its request is
+    /// done (or has failed).
+    fn execute_batch(&self, batch: &[Action]) -> Result<()> {
+        let _spans: Vec<Span> = batch
+            .iter()
+            .map(|action| {
+                let span = tracing::info_span!(
+                    "action",
+                    action = action.kind(),
+                    path = action.entry().path.as_str(),
+                    bytes = action.bytes()
+                );
+                span.in_scope(|| tracing::info!("Action: {}", action));
+                self.progress.event(action, &Event::Started);
+                span
+            })
+            .collect();
+        let start = Instant::now();
+        let result = self.apply_with_retries(batch);
+        let duration = start.elapsed();
+        match result {
+            Ok(()) => {
+                for action in batch {
+                    self.progress.event(action, &Event::Completed { duration });
+                }
+                self.progress.completed(batch);
+                Ok(())
+            }
+            Err(e) => {
+                let event = Event::Failed {
+                    duration,
+                    error: e.to_string(),
+                };
+                for action in batch {
+                    self.progress.event(action, &event);
+                }
+                if self.options.policy(&batch[0]) != ErrorPolicy::Continue {
+                    return Err(e);
+                }
+                tracing::error!("{}", e);
+                let mut failures = self.failures.lock().unwrap();
+                failures.extend(batch.iter().map(|action| (action.clone(), e.to_string())));
+                Ok(())
+            }
+        }
+    }
+
+    /// Apply a batch of actions, retrying up to `max_retries` times if it fails (or more, under
+    /// [`ErrorPolicy::Retry`]).
+    ///
+    /// No more retries are made once the deploy is cancelled.
+    fn apply_with_retries(&self, batch: &[Action]) -> Result<()> {
+        let max_retries = match self.options.policy(&batch[0]) {
+            ErrorPolicy::Retry => self.options.max_retries.max(POLICY_RETRIES),
+            _ => self.options.max_retries,
+        };
+        let mut delay = self.options.retry_delay;
+        let mut retries = 0;
+        loop {
+            match self.apply_batch(batch) {
+                Err(e) if retries < max_retries && !self.control.is_cancelled() => {
+                    retries += 1;
+                    tracing::warn!(
+                        "{}; retrying in {:?} ({} of {})",
+                        e,
+                        delay,
+                        retries,
+                        max_retries
+                    );
+                    thread::sleep(delay);
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Apply a batch of actions to the client.
+    ///
+    /// A batch is either a single action or a sequence of uploads, which are sent together in one
+    /// request. The files of the uploads are only read once their size fits in the memory budget
+    /// (see [`ExecuteOptions::max_memory`]), and released as soon as the request is done.
+    fn apply_batch(&self, batch: &[Action]) -> Result<()> {
+        if self.options.dry_run {
+            return Ok(());
+        }
+        match batch {
+            [Action::DeleteRemote(entry)] => {
+                self.client.delete(&[&entry.path])?;
+                Ok(())
+            }
+            uploads => {
+                let _memory = self.memory.acquire(uploads.iter().map(Action::bytes).sum());
+                let files = uploads
+                    .iter()
+                    .map(|action| {
+                        let entry = action.entry();
+                        Ok((entry.path.as_str(), entry.read()?))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let files: Vec<_> = files
+                    .iter()
+                    .map(|(path, file)| (*path, &file[..]))
+                    .collect();
+                self.client.upload(&files)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trees::{Entry, FileInfo};
+    use neocities_client::Auth;
+
+    fn upload(path: &str, size: u64) -> Action {
+        Action::Upload(Entry {
+            path: path.to_owned(),
+            info: Some(FileInfo {
+                size,
+                sha1_sum: String::new(),
+                modified: None,
+            }),
+            local_path: None,
+            content: Some(Vec::new().into()),
+        })
+    }
+
+    fn delete(path: &str) -> Action {
+        Action::DeleteRemote(Entry {
+            path: path.to_owned(),
+            info: None,
+            local_path: None,
+            content: None,
+        })
+    }
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl Progress for Recorder {
+        fn event(&self, action: &Action, event: &Event) {
+            let record = format!("{} {}", event.name(), action.entry().path);
+            self.0.lock().unwrap().push(record);
+        }
+
+        fn completed(&self, batch: &[Action]) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("batch of {}", batch.len()));
+        }
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let client = Client::builder()
+            .auth(Auth::from("api_key"))
+            .build()
+            .unwrap();
+        let options = ExecuteOptions {
+            batch_bytes: 10,
+            dry_run: true,
+            ..ExecuteOptions::default()
+        };
+        let actions = [upload("a.css", 6), upload("b.css", 6), upload("c.html", 1)];
+        let recorder = Recorder::default();
+        let outcome = Executor::new(&client, options)
+            .progress(&recorder)
+            .execute(&actions)
+            .unwrap();
+        assert_eq!(outcome.done, 3);
+        assert_eq!(
+            outcome.summary(&actions),
+            Summary {
+                uploaded: 3,
+                bytes: 13,
+                ..Summary::default()
+            }
+        );
+        assert_eq!(
+            recorder.0.into_inner().unwrap(),
+            [
+                "planned a.css",
+                "planned b.css",
+                "planned c.html",
+                "started a.css",
+                "completed a.css",
+                "batch of 1",
+                "started b.css",
+                "completed b.css",
+                "batch of 1",
+                "started c.html",
+                "completed c.html",
+                "batch of 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let budget = MemoryBudget::new(Some(10));
+        let first = budget.acquire(6);
+        thread::scope(|scope| {
+            let waiting = scope.spawn(|| {
+                let _second = budget.acquire(6);
+                assert_eq!(*budget.used(), 6);
+            });
+            thread::sleep(Duration::from_millis(50));
+            assert!(!waiting.is_finished());
+            drop(first);
+            waiting.join().unwrap();
+        });
+        // A file larger than the limit is still read, alone.
+        let large = budget.acquire(20);
+        assert_eq!(*budget.used(), 20);
+        drop(large);
+        assert_eq!(*budget.used(), 0);
+    }
+
+    #[test]
+    fn test_batches_within_memory_budget() {
+        let client = Client::builder()
+            .auth(Auth::from("api_key"))
+            .build()
+            .unwrap();
+        let options = ExecuteOptions {
+            batch_bytes: 100,
+            max_memory: Some(10),
+            ..ExecuteOptions::default()
+        };
+        let executor = Executor::new(&client, options);
+        let actions = [
+            upload("a.css", 6),
+            upload("b.css", 6),
+            upload("c.css", 20),
+            upload("d.css", 2),
+            upload("e.css", 2),
+        ];
+        let sizes: Vec<_> = executor.batches(&actions).iter().map(|b| b.len()).collect();
+        assert_eq!(sizes, [1, 1, 1, 2]);
+    }
+
+    #[test]
+    fn test_cancelled() {
+        let client = Client::builder()
+            .auth(Auth::from("api_key"))
+            .build()
+            .unwrap();
+        let options = ExecuteOptions {
+            dry_run: true,
+            ..ExecuteOptions::default()
+        };
+        let control = Control::new();
+        control.cancel();
+        let actions = [upload("a.css", 1)];
+        let outcome = Executor::new(&client, options)
+            .control(control)
+            .execute(&actions)
+            .unwrap();
+        assert_eq!(outcome.done, 0);
+    }
+
+    #[test]
+    fn test_policy_by_kind() {
+        let mut builder = Client::builder();
+        builder
+            .auth(Auth::from("api_key"))
+            .base_url("http://127.0.0.1:1".to_owned());
+        let client = builder.build().unwrap();
+        let options = ExecuteOptions {
+            on_delete_error: Some(ErrorPolicy::Continue),
+            retry_delay: Duration::ZERO,
+            ..ExecuteOptions::default()
+        };
+        assert_eq!(options.policy(&upload("a.css", 1)), ErrorPolicy::Abort);
+        assert_eq!(options.policy(&delete("b.css")), ErrorPolicy::Continue);
+
+        let actions = [delete("b.css")];
+        let outcome = Executor::new(&client, options.clone())
+            .execute(&actions)
+            .unwrap();
+        assert_eq!(outcome.failures.len(), 1);
+        let actions = [upload("a.css", 1)];
+        assert!(Executor::new(&client, options).execute(&actions).is_err());
+    }
+
+    #[test]
+    fn test_skipped_after_error() {
+        let mut builder = Client::builder();
+        builder
+            .auth(Auth::from("api_key"))
+            .base_url("http://127.0.0.1:1".to_owned());
+        let client = builder.build().unwrap();
+        let options = ExecuteOptions {
+            batch_files: 1,
+            on_delete_error: Some(ErrorPolicy::Continue),
+            retry_delay: Duration::ZERO,
+            ..ExecuteOptions::default()
+        };
+        let actions = [
+            delete("a.css"),
+            upload("b.css", 1),
+            upload("c.css", 1),
+            upload("d.html", 1),
+        ];
+        let recorder = Recorder::default();
+        let result = Executor::new(&client, options)
+            .progress(&recorder)
+            .execute(&actions);
+        assert!(result.is_err());
+        assert_eq!(
+            recorder.0.into_inner().unwrap(),
+            [
+                "planned a.css",
+                "planned b.css",
+                "planned c.css",
+                "planned d.html",
+                "started a.css",
+                "failed a.css",
+                "started b.css",
+                "failed b.css",
+                "skipped c.css",
+                "skipped d.html",
+            ]
+        );
+    }
+}
@@ -0,0 +1,30 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! The sync engine of [neocities-deploy](https://github.com/kugland/neocities-deploy), for tools
+//! that want to deploy Neocities sites without shelling out to the command-line tool.
+//!
+//! A deploy goes in three steps: build the local tree from one or more [`trees::TreeSource`]s
+//! and the remote one from the site’s file list, [`plan::Plan::compare`] the two, and run the
+//! resulting actions with an [`executor::Executor`], which reports its progress through the
+//! [`executor::Progress`] trait and can be paused or cancelled with a [`control::Control`].
+
+pub mod control;
+pub mod executor;
+pub mod plan;
+pub mod trees;
@@ -0,0 +1,371 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Working out the actions that bring a site in line with a local tree.
+
+use crate::trees::Entry;
+use itertools::{EitherOrBoth::*, Itertools};
+use parse_display::Display;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Display, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Actions to deploy the local tree to the site.
+pub enum Action {
+    /// Upload a file to the remote entry.
+    #[display("upload {0.path}")]
+    Upload(Entry),
+    /// Delete a remote entry.
+    #[display("delete remote {0.path}")]
+    DeleteRemote(Entry),
+}
+
+impl Action {
+    /// Entry the action applies to.
+    pub fn entry(&self) -> &Entry {
+        match self {
+            Action::Upload(entry) | Action::DeleteRemote(entry) => entry,
+        }
+    }
+
+    /// Short machine-readable name of the kind of action.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Action::Upload(_) => "upload",
+            Action::DeleteRemote(_) => "delete",
+        }
+    }
+
+    /// Whether the action applies to an HTML file.
+    pub fn is_html(&self) -> bool {
+        let path = self.entry().path.to_ascii_lowercase();
+        path.ends_with(".html") || path.ends_with(".htm")
+    }
+
+    /// Number of bytes the action transfers.
+    pub fn bytes(&self) -> u64 {
+        match self {
+            Action::Upload(entry) => entry.info.as_ref().map_or(0, |info| info.size),
+            Action::DeleteRemote(_) => 0,
+        }
+    }
+}
+
+/// Orders in which the actions of a deploy can be run.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum DeployOrder {
+    /// Upload all files before deleting anything, so that an interrupted deploy never leaves
+    /// links to deleted files. Deletions that must happen before an upload (a remote file where
+    /// a local directory now is, or vice versa) still run first.
+    #[default]
+    UploadsFirst,
+    /// Delete files before uploading anything.
+    DeletionsFirst,
+    /// Run uploads and deletions in the order of their paths.
+    ByPath,
+}
+
+/// Ways of deciding whether a local file differs from the remote one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+pub enum Compare {
+    /// Compare sizes and SHA-1 hashes; the local files must be hashed.
+    #[default]
+    Hash,
+    /// Compare sizes, and consider a file changed if it was modified after the remote one was
+    /// last updated; the local files are not hashed. Files whose modification time is unknown
+    /// (like those read from archives) are compared by hash if one is at hand.
+    Fast,
+    /// Consider every file changed, uploading the whole site.
+    Force,
+}
+
+impl Compare {
+    /// Whether the local tree must be hashed for this comparison.
+    pub fn needs_hash(self) -> bool {
+        self == Compare::Hash
+    }
+
+    /// Check whether a local file is unchanged from the remote one.
+    pub fn is_unchanged(self, local: &Entry, remote: &Entry) -> bool {
+        match (self, &local.info, &remote.info) {
+            (Compare::Force, _, _) => false,
+            (Compare::Fast, Some(l), Some(r)) => match (l.modified, r.modified) {
+                (Some(l_time), Some(r_time)) => l.size == r.size && l_time <= r_time,
+                _ => local.is_same(remote),
+            },
+            _ => local.is_same(remote),
+        }
+    }
+}
+
+/// The actions of a deploy, in the order they should run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Plan {
+    /// The actions to run.
+    pub actions: Vec<Action>,
+}
+
+impl Plan {
+    /// Compare a local tree with a remote one and work out the actions that make the remote tree
+    /// match the local one, using `compare` to decide which files have changed.
+    ///
+    /// **Note:** This function assumes that the two trees are sorted by path. Both
+    /// [`mounted_tree`](crate::trees::mounted_tree) and
+    /// [`remote_tree`](crate::trees::remote_tree) return sorted trees, so this should be a safe
+    /// assumption.
+    pub fn compare(local: Vec<Entry>, remote: Vec<Entry>, compare: Compare) -> Self {
+        use Action::*;
+
+        let actions = local
+            .into_iter()
+            .merge_join_by(remote, |a, b| a.path.cmp(&b.path))
+            .flat_map(|pair| match pair {
+                // Local is a file, remote has no entry: upload.
+                Left(l) if l.is_file() => vec![Upload(l)],
+                // Local is a directory, remote has no entry: do nothing.
+                Left(_) => vec![],
+                // Local has no entry, remote is either a file or a directory: delete remote.
+                Right(r) => vec![DeleteRemote(r)],
+                // Now for the cases where we have both local and remote entries:
+                Both(l, r) => match (l.is_file(), r.is_file(), compare.is_unchanged(&l, &r)) {
+                    // Remote is a file, local is a directory: delete remote.
+                    (false, true, _) => vec![DeleteRemote(r)],
+                    // Local is a file, remote is a directory: delete remote, upload.
+                    (true, false, _) => vec![DeleteRemote(r), Upload(l)],
+                    // Both are files, but different: upload.
+                    (true, true, false) => vec![Upload(l)],
+                    // Otherwise, do nothing.
+                    _ => vec![],
+                },
+            })
+            .fold(Vec::new(), |mut acc, action| {
+                // After the deletion of a directory, skip the deletion of its children; otherwise,
+                // we would get errors because the children would already be gone when we try to
+                // delete them.
+                match (acc.last(), &action) {
+                    (Some(DeleteRemote(last)), DeleteRemote(cur))
+                        if cur.path.starts_with(&format!("{}/", last.path)) => {}
+                    _ => acc.push(action),
+                };
+                acc
+            });
+        Self { actions }
+    }
+
+    /// Reorder the actions, keeping the relative order of uploads and of deletions.
+    ///
+    /// If `html_last` is set, HTML files are uploaded after all other files, so that pages never
+    /// reference assets that are not there yet; this does not apply to [`DeployOrder::ByPath`].
+    pub fn reorder(self, order: DeployOrder, html_last: bool) -> Self {
+        let (mut uploads, deletions): (Vec<_>, Vec<_>) = self
+            .actions
+            .into_iter()
+            .partition(|action| matches!(action, Action::Upload(_)));
+        if html_last && order != DeployOrder::ByPath {
+            uploads.sort_by_key(Action::is_html);
+        }
+        let actions = match order {
+            DeployOrder::ByPath => {
+                let mut actions = [deletions, uploads].concat();
+                // Stable, so a deletion still precedes an upload of the same path.
+                actions.sort_by(|a, b| a.entry().path.cmp(&b.entry().path));
+                actions
+            }
+            DeployOrder::DeletionsFirst => [deletions, uploads].concat(),
+            DeployOrder::UploadsFirst => {
+                // A deletion must go first if an upload is to the same path or below it.
+                let (before, after): (Vec<_>, Vec<_>) = deletions.into_iter().partition(|d| {
+                    let path = &d.entry().path;
+                    let prefix = format!("{}/", path);
+                    uploads.iter().any(|u| {
+                        let upload = &u.entry().path;
+                        upload == path || upload.starts_with(&prefix)
+                    })
+                });
+                [before, uploads, after].concat()
+            }
+        };
+        Self { actions }
+    }
+}
+
+impl From<Vec<Action>> for Plan {
+    fn from(actions: Vec<Action>) -> Self {
+        Self { actions }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::assert_equal;
+
+    fn upload(path: &str) -> Action {
+        Action::Upload(Entry {
+            path: path.to_owned(),
+            info: None,
+            local_path: None,
+            content: None,
+        })
+    }
+
+    fn delete(path: &str) -> Action {
+        Action::DeleteRemote(Entry {
+            path: path.to_owned(),
+            info: None,
+            local_path: None,
+            content: None,
+        })
+    }
+
+    #[test]
+    fn test_reorder() {
+        let actions = vec![
+            delete("a.html"),
+            delete("b"),
+            upload("b/c.html"),
+            upload("d.html"),
+            delete("e.html"),
+            upload("e.html/f.html"),
+        ];
+        let paths = |order| {
+            Plan::from(actions.clone())
+                .reorder(order, false)
+                .actions
+                .into_iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_equal(
+            paths(DeployOrder::UploadsFirst),
+            [
+                "delete remote b",
+                "delete remote e.html",
+                "upload b/c.html",
+                "upload d.html",
+                "upload e.html/f.html",
+                "delete remote a.html",
+            ],
+        );
+        assert_equal(
+            paths(DeployOrder::DeletionsFirst),
+            [
+                "delete remote a.html",
+                "delete remote b",
+                "delete remote e.html",
+                "upload b/c.html",
+                "upload d.html",
+                "upload e.html/f.html",
+            ],
+        );
+        assert_equal(
+            paths(DeployOrder::ByPath),
+            actions.iter().map(|a| a.to_string()),
+        );
+    }
+
+    #[test]
+    fn test_reorder_html_last() {
+        let actions = vec![
+            upload("a.html"),
+            upload("a.css"),
+            delete("b.png"),
+            upload("c/index.HTM"),
+            upload("c/d.png"),
+        ];
+        assert_equal(
+            Plan::from(actions)
+                .reorder(DeployOrder::UploadsFirst, true)
+                .actions
+                .into_iter()
+                .map(|a| a.to_string()),
+            [
+                "upload a.css",
+                "upload c/d.png",
+                "upload a.html",
+                "upload c/index.HTM",
+                "delete remote b.png",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_compare() {
+        let file = |path: &str, sha1_sum: &str| Entry {
+            path: path.to_owned(),
+            info: Some(crate::trees::FileInfo {
+                size: 1,
+                sha1_sum: sha1_sum.to_owned(),
+                modified: None,
+            }),
+            local_path: None,
+            content: None,
+        };
+        let dir = |path: &str| Entry {
+            path: path.to_owned(),
+            info: None,
+            local_path: None,
+            content: None,
+        };
+        let local = vec![file("a", "1"), file("b", "2"), dir("c"), file("d", "4")];
+        let remote = vec![
+            file("a", "1"),
+            file("b", "0"),
+            file("c", "3"),
+            dir("e"),
+            file("e/f", "5"),
+        ];
+        assert_equal(
+            Plan::compare(local, remote, Compare::Hash)
+                .actions
+                .into_iter()
+                .map(|a| a.to_string()),
+            ["upload b", "delete remote c", "upload d", "delete remote e"],
+        );
+    }
+
+    #[test]
+    fn test_compare_strategies() {
+        let file = |size: u64, sha1_sum: &str, modified: Option<i64>| Entry {
+            path: "a".to_owned(),
+            info: Some(crate::trees::FileInfo {
+                size,
+                sha1_sum: sha1_sum.to_owned(),
+                modified,
+            }),
+            local_path: None,
+            content: None,
+        };
+        let remote = file(1, "1", Some(100));
+        // Same hash.
+        assert!(Compare::Hash.is_unchanged(&file(1, "1", Some(200)), &remote));
+        assert!(!Compare::Hash.is_unchanged(&file(1, "2", Some(50)), &remote));
+        // Not modified since the upload, and the same size.
+        assert!(Compare::Fast.is_unchanged(&file(1, "", Some(100)), &remote));
+        assert!(!Compare::Fast.is_unchanged(&file(1, "", Some(101)), &remote));
+        assert!(!Compare::Fast.is_unchanged(&file(2, "", Some(50)), &remote));
+        // No modification time: fall back to the hash.
+        assert!(Compare::Fast.is_unchanged(&file(1, "1", None), &remote));
+        assert!(!Compare::Fast.is_unchanged(&file(1, "", None), &remote));
+        // Everything is changed.
+        assert!(!Compare::Force.is_unchanged(&file(1, "1", Some(50)), &remote));
+    }
+}
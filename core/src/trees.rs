@@ -0,0 +1,1182 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::DateTime;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::GitignoreBuilder;
+use itertools::Itertools;
+use neocities_client::response::ListEntry;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use std::{fs, io};
+use unicode_normalization::UnicodeNormalization;
+
+const NEOCITIES_IGNORE: &str = ".neocitiesignore";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Path of the entry, relative to the root of the tree.
+    pub path: String,
+    /// Information about the file, if it is a file.
+    pub info: Option<FileInfo>,
+    /// Full path to the file on the local file system, if it is local.
+    pub local_path: Option<PathBuf>,
+    /// Contents of the file, when it was read from an archive rather than the file system.
+    #[serde(skip)]
+    pub content: Option<Arc<[u8]>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileInfo {
+    /// Size of the file in bytes.
+    pub size: u64,
+    /// SHA-1 hash of the file.
+    pub sha1_sum: String,
+    /// Time of the last modification of the file, in seconds since the Unix epoch, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<i64>,
+}
+
+impl Entry {
+    /// Test whether the entry is a file.
+    pub fn is_file(&self) -> bool {
+        self.info.is_some()
+    }
+
+    /// Check whether two entries have the same content.
+    pub fn is_same(&self, other: &Self) -> bool {
+        match (&self.info, &other.info) {
+            (Some(a), Some(b)) => a.size == b.size && a.sha1_sum == b.sha1_sum,
+            (a, b) => a.is_none() && b.is_none(),
+        }
+    }
+
+    /// Read the contents of a local file, from memory or from the file system.
+    pub fn read(&self) -> Result<Cow<'_, [u8]>> {
+        match (&self.content, &self.local_path) {
+            (Some(content), _) => Ok(Cow::Borrowed(content)),
+            (None, Some(local_path)) => Ok(Cow::Owned(fs::read(local_path)?)),
+            (None, None) => bail!("No local contents for {}", self.path),
+        }
+    }
+
+    /// Create a new `Entry` from the local file system, hashing its contents if `hash` is set.
+    fn local(
+        root: &Path,
+        entry: &ignore::DirEntry,
+        hash: bool,
+        options: &TreeOptions,
+    ) -> Result<Self> {
+        let local_path = entry.path();
+        let path = local_path
+            .strip_prefix(root)
+            .unwrap_or_else(|_| entry.path())
+            .to_str()
+            .ok_or(anyhow!("Non-UTF-8 path: {:?}", entry.path()))?
+            .to_owned();
+        let path = if MAIN_SEPARATOR != '/' {
+            // Replace Windows path separators with Unix ones.
+            path.replace(MAIN_SEPARATOR, "/")
+        } else {
+            path
+        };
+        let path = if options.normalize_unicode {
+            // macOS keeps file names decomposed (NFD), but most everything else uses NFC.
+            path.nfc().collect()
+        } else {
+            path
+        };
+        let local_path = Some(local_path.canonicalize()?);
+        let metadata = entry.metadata()?;
+        let info = if !metadata.is_dir() {
+            let size = metadata.len();
+            let sha1_sum = if hash {
+                sha1_sum(entry.path())?
+            } else {
+                String::new()
+            };
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs() as i64);
+            Some(FileInfo {
+                size,
+                sha1_sum,
+                modified,
+            })
+        } else {
+            None
+        };
+        Ok(Self {
+            path,
+            local_path,
+            info,
+            content: None,
+        })
+    }
+}
+
+// Conversion of API’s `ListEntry` to `Entry`.
+impl From<&ListEntry> for Entry {
+    fn from(entry: &ListEntry) -> Self {
+        Self {
+            path: entry.path.clone(),
+            info: if entry.is_directory {
+                None
+            } else {
+                Some(FileInfo {
+                    size: entry.size.expect("Entry has no size"),
+                    sha1_sum: (entry.sha1_hash.clone()).expect("Entry has no SHA-1 hash"),
+                    modified: DateTime::parse_from_rfc2822(&entry.updated_at)
+                        .ok()
+                        .map(|time| time.timestamp()),
+                })
+            },
+            local_path: None,
+            content: None,
+        }
+    }
+}
+
+/// Compute the SHA-1 hash of some contents, as a hexadecimal string.
+pub fn sha1_bytes(content: &[u8]) -> String {
+    format!("{:x}", Sha1::digest(content))
+}
+
+/// Size from which files are memory-mapped to hash them, with the `mmap` feature.
+#[cfg(all(unix, feature = "mmap"))]
+const MMAP_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// Compute the SHA-1 hash of a file, as a hexadecimal string.
+///
+/// With the `mmap` feature, files of 16 MiB or more are memory-mapped instead of read, which
+/// saves copying them through a buffer.
+pub fn sha1_sum(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    #[cfg(all(unix, feature = "mmap"))]
+    {
+        let len = file.metadata()?.len();
+        if len >= MMAP_THRESHOLD {
+            return sha1_mapped(&file, len);
+        }
+    }
+    let mut hasher = Sha1::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Compute the SHA-1 hash of a file by memory-mapping it.
+#[cfg(all(unix, feature = "mmap"))]
+fn sha1_mapped(file: &fs::File, len: u64) -> Result<String> {
+    use rustix::mm::{madvise, mmap, munmap, Advice, MapFlags, ProtFlags};
+    use std::ptr;
+
+    let len = usize::try_from(len)?;
+    // SAFETY: the mapping is private, read-only, and unmapped before returning, and the slice
+    // does not outlive it. A file truncated while it is being hashed makes the process crash
+    // with `SIGBUS`, which is the price of not reading it.
+    unsafe {
+        let addr = mmap(
+            ptr::null_mut(),
+            len,
+            ProtFlags::READ,
+            MapFlags::PRIVATE,
+            file,
+            0,
+        )?;
+        // The advice only makes reading faster, so it may fail.
+        let _ = madvise(addr, len, Advice::Sequential);
+        let hash = sha1_bytes(std::slice::from_raw_parts(addr.cast::<u8>(), len));
+        munmap(addr, len)?;
+        Ok(hash)
+    }
+}
+
+/// Create a tree from a list of [`ListEntry`] from the API.
+pub fn remote_tree(list: &[ListEntry]) -> Vec<Entry> {
+    let mut res: Vec<_> = list.iter().map(Entry::from).collect();
+    res.sort_by(|a, b| a.path.cmp(&b.path));
+    res
+}
+
+/// Options for building a local file tree.
+#[derive(Clone, Debug)]
+pub struct TreeOptions {
+    /// Whether to normalize paths to Unicode NFC.
+    pub normalize_unicode: bool,
+    /// Whether to follow symbolic links; if not, they are left out of the tree.
+    pub follow_symlinks: bool,
+    /// Whether to honor `.gitignore` files, in addition to `.neocitiesignore` files.
+    pub use_gitignore: bool,
+    /// Additional patterns of files to leave out, in `.gitignore` syntax, relative to the root.
+    pub ignore: Vec<String>,
+    /// Whether to include hidden files and directories (those whose names start with a dot).
+    pub include_hidden: bool,
+    /// Rules that move local paths (and everything under them) to different remote paths, tried
+    /// in order.
+    pub map: Vec<(String, String)>,
+}
+
+impl Default for TreeOptions {
+    fn default() -> Self {
+        Self {
+            normalize_unicode: true,
+            follow_symlinks: true,
+            use_gitignore: false,
+            ignore: Vec::new(),
+            include_hidden: true,
+            map: Vec::new(),
+        }
+    }
+}
+
+/// Something a local file tree can be built from: a directory ([`PathBuf`]), an archive or a
+/// tree generated in memory ([`MemoryTree`]).
+pub trait TreeSource {
+    /// Build the tree, with paths relative to the root of the source and sorted, hashing the files
+    /// if `hash` is set (otherwise their SHA-1 sums may be left empty).
+    ///
+    /// The tree includes files that free accounts cannot upload; use [`remove_disallowed`] to take
+    /// them out.
+    fn tree(&self, hash: bool, options: &TreeOptions) -> Result<Vec<Entry>>;
+}
+
+impl TreeSource for PathBuf {
+    fn tree(&self, hash: bool, options: &TreeOptions) -> Result<Vec<Entry>> {
+        walk(self.clone(), hash, options)
+    }
+}
+
+/// A file tree kept in memory, such as the contents of an archive or generated files.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryTree {
+    /// Paths and contents of the files.
+    pub files: BTreeMap<String, Arc<[u8]>>,
+    /// Paths of the directories; the directories of the files need not be listed.
+    pub dirs: BTreeSet<String>,
+}
+
+impl MemoryTree {
+    /// Add a file to the tree, replacing any file with the same path.
+    pub fn add_file(&mut self, path: impl Into<String>, content: impl Into<Arc<[u8]>>) {
+        self.files.insert(path.into(), content.into());
+    }
+
+    /// Add a directory to the tree.
+    pub fn add_dir(&mut self, path: impl Into<String>) {
+        self.dirs.insert(path.into());
+    }
+}
+
+/// The options apply as they do to local directories, except that `.neocitiesignore` and
+/// `.gitignore` files in the tree are not honored, and there are no links to follow. Files are
+/// always hashed, since their contents are at hand.
+impl TreeSource for MemoryTree {
+    fn tree(&self, _hash: bool, options: &TreeOptions) -> Result<Vec<Entry>> {
+        let mut patterns = GitignoreBuilder::new("");
+        for pattern in &options.ignore {
+            patterns.add_line(None, pattern)?;
+        }
+        let patterns = patterns.build()?;
+        let normalize = |path: &str| -> String {
+            let path = path.trim_start_matches("./").trim_end_matches('/');
+            if options.normalize_unicode {
+                path.nfc().collect()
+            } else {
+                path.to_owned()
+            }
+        };
+        let included = |path: &str, is_dir: bool| {
+            !path.is_empty()
+                && !path.ends_with(NEOCITIES_IGNORE)
+                && (options.include_hidden || !path.split('/').any(|c| c.starts_with('.')))
+                && !patterns
+                    .matched_path_or_any_parents(path, is_dir)
+                    .is_ignore()
+        };
+
+        let dirs = self.dirs.iter().map(|path| Entry {
+            path: normalize(path),
+            info: None,
+            local_path: None,
+            content: None,
+        });
+        let files = self.files.iter().map(|(path, content)| Entry {
+            path: normalize(path),
+            info: Some(FileInfo {
+                size: content.len() as u64,
+                sha1_sum: sha1_bytes(content),
+                modified: None,
+            }),
+            local_path: None,
+            content: Some(content.clone()),
+        });
+        let mut tree: Vec<_> = dirs
+            .chain(files)
+            .filter(|e| included(&e.path, !e.is_file()))
+            .collect();
+        check_paths(&tree)?;
+        tree.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(tree)
+    }
+}
+
+/// Restrict a remote tree to the entries under a prefix (and the directories leading to it), so
+/// that deploying to the prefix leaves the rest of the site alone. An empty prefix keeps the
+/// whole tree.
+pub fn scope_remote(remote: &mut Vec<Entry>, prefix: &str) {
+    if prefix.is_empty() {
+        return;
+    }
+    let prefix = format!("{}/", prefix);
+    remote.retain(|e| e.path.starts_with(&prefix) || prefix.starts_with(&format!("{}/", e.path)));
+}
+
+/// Take out of a remote tree the entries matching `is_protected` that have no local counterpart,
+/// along with the directories containing them, so that a deploy never deletes them. Returns the
+/// paths of the protected entries that were taken out.
+pub fn protect_remote(
+    local: &[Entry],
+    remote: &mut Vec<Entry>,
+    is_protected: impl Fn(&str) -> bool,
+) -> Vec<String> {
+    let local: HashSet<_> = local.iter().map(|e| e.path.as_str()).collect();
+    let (protected, kept): (Vec<_>, Vec<_>) = remote
+        .drain(..)
+        .partition(|e| !local.contains(e.path.as_str()) && is_protected(&e.path));
+    *remote = kept
+        .into_iter()
+        .filter(|e| {
+            let prefix = format!("{}/", e.path);
+            e.is_file() || !protected.iter().any(|p| p.path.starts_with(&prefix))
+        })
+        .collect();
+    protected.into_iter().map(|e| e.path).collect()
+}
+
+/// Find the directories of a remote tree that contain no file, leaving out the directories that
+/// do not lie under `prefix` (the site root itself is never returned) and the ones matching
+/// `is_protected`. Only the topmost empty directories are returned, since deleting a directory
+/// deletes everything under it.
+pub fn empty_remote_dirs(
+    remote: &[Entry],
+    prefix: &str,
+    is_protected: impl Fn(&str) -> bool,
+) -> Vec<String> {
+    let prefix = if prefix.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", prefix)
+    };
+    let mut empty: Vec<String> = Vec::new();
+    for dir in remote.iter().filter(|e| !e.is_file()) {
+        let inner = format!("{}/", dir.path);
+        if !dir.path.starts_with(&prefix)
+            || is_protected(&dir.path)
+            || empty.iter().any(|e| inner.starts_with(&format!("{}/", e)))
+        {
+            continue;
+        }
+        let has_files = remote
+            .iter()
+            .any(|e| e.is_file() && e.path.starts_with(&inner));
+        let has_protected = remote
+            .iter()
+            .any(|e| e.path.starts_with(&inner) && is_protected(&e.path));
+        if !has_files && !has_protected {
+            empty.push(dir.path.clone());
+        }
+    }
+    empty
+}
+
+/// Remove from a tree the files that the account is not allowed to upload, as decided by
+/// `is_allowed` (usually the site configuration’s extension rules), returning them.
+pub fn remove_disallowed(tree: &mut Vec<Entry>, is_allowed: impl Fn(&str) -> bool) -> Vec<Entry> {
+    let (allowed, disallowed) = tree
+        .drain(..)
+        .partition(|e| !e.is_file() || is_allowed(&e.path));
+    *tree = allowed;
+    disallowed
+}
+
+/// A local directory whose files are deployed under a prefix of the site.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceDir {
+    /// Path to the local directory.
+    pub root: PathBuf,
+    /// Remote directory the files go in, without leading or trailing slashes; empty for the root
+    /// of the site.
+    pub prefix: String,
+}
+
+impl SourceDir {
+    /// Path on the site of a file, given its path relative to the local directory and the `map`
+    /// rules of the tree options.
+    pub fn remote_path(&self, path: &str, map: &[(String, String)]) -> String {
+        mounted_path(&self.prefix, path, map)
+    }
+}
+
+/// Create a local file tree from several local directories, each one under its prefix, hashing
+/// the files if `hash` is set. See [`mounted_tree`].
+pub fn merged_tree(sources: &[SourceDir], hash: bool, options: &TreeOptions) -> Result<Vec<Entry>> {
+    let sources: Vec<_> = sources
+        .iter()
+        .map(|s| (&s.root as &dyn TreeSource, s.prefix.as_str()))
+        .collect();
+    mounted_tree(&sources, hash, options)
+}
+
+/// Create a local file tree from several tree sources, each one under its prefix (without
+/// leading or trailing slashes; empty for the root of the site), hashing the files if `hash` is
+/// set.
+///
+/// The `map` rules of the options are applied to the paths of each source before its prefix is
+/// added. Sources may overlap, but it is an error for two files to end up at the same path.
+pub fn mounted_tree(
+    sources: &[(&dyn TreeSource, &str)],
+    hash: bool,
+    options: &TreeOptions,
+) -> Result<Vec<Entry>> {
+    check_map(options)?;
+    let mut tree = Vec::new();
+    for (source, prefix) in sources {
+        if !prefix.is_empty() {
+            if let Some(problem) = path_problem(prefix) {
+                bail!("Invalid prefix {:?}: {}", prefix, problem);
+            }
+            // The directories of the prefix itself, so that they are not deleted from the site.
+            let components: Vec<_> = prefix.split('/').collect();
+            tree.extend((1..=components.len()).map(|n| Entry {
+                path: components[..n].join("/"),
+                info: None,
+                local_path: None,
+                content: None,
+            }));
+        }
+        for mut entry in source.tree(hash, options)? {
+            entry.path = mounted_path(prefix, &entry.path, &options.map);
+            if !entry.path.is_empty() {
+                tree.push(entry);
+            }
+        }
+    }
+    finish(tree)
+}
+
+/// Path on the site of a file, given its path relative to a source mounted at `prefix` and the
+/// `map` rules of the tree options.
+fn mounted_path(prefix: &str, path: &str, map: &[(String, String)]) -> String {
+    let path = remap(path, map).unwrap_or_else(|| path.to_owned());
+    match (prefix.is_empty(), path.is_empty()) {
+        (true, _) => path,
+        (false, true) => prefix.to_owned(),
+        (false, false) => format!("{}/{}", prefix, path),
+    }
+}
+
+/// Check that the paths of the `map` rules are safe.
+fn check_map(options: &TreeOptions) -> Result<()> {
+    for (from, to) in &options.map {
+        for path in [from, to].into_iter().filter(|p| !p.is_empty()) {
+            if let Some(problem) = path_problem(path) {
+                bail!("Invalid path {:?} in map: {}", path, problem);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sort a tree assembled from several parts, failing if two files ended up at the same path, and
+/// add the directories missing from it.
+fn finish(mut tree: Vec<Entry>) -> Result<Vec<Entry>> {
+    tree.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut collisions = Vec::new();
+    tree.dedup_by(|cur, prev| {
+        if cur.path != prev.path {
+            return false;
+        }
+        if cur.is_file() || prev.is_file() {
+            let origin = |e: &Entry| e.local_path.clone().unwrap_or_else(|| "archive".into());
+            collisions.push(format!(
+                "  {:?}: {:?} and {:?}",
+                cur.path,
+                origin(prev),
+                origin(cur)
+            ));
+        }
+        true
+    });
+    if !collisions.is_empty() {
+        bail!(
+            "{} path(s) would be uploaded from more than one local file:\n{}",
+            collisions.len(),
+            collisions.join("\n")
+        );
+    }
+
+    // Remapped paths may land in directories that do not exist locally.
+    let paths: HashSet<_> = tree.iter().map(|e| e.path.clone()).collect();
+    let missing: BTreeSet<_> = tree
+        .iter()
+        .flat_map(|e| parents(&e.path))
+        .filter(|p| !paths.contains(*p))
+        .map(str::to_owned)
+        .collect();
+    if !missing.is_empty() {
+        tree.extend(missing.into_iter().map(|path| Entry {
+            path,
+            info: None,
+            local_path: None,
+            content: None,
+        }));
+        tree.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+
+    Ok(tree)
+}
+
+/// Rewrite a path with the first rule of `map` that matches it or one of its ancestors.
+fn remap(path: &str, map: &[(String, String)]) -> Option<String> {
+    map.iter().find_map(|(from, to)| {
+        let rest = path.strip_prefix(from.as_str())?;
+        match rest.strip_prefix('/') {
+            _ if rest.is_empty() => Some(to.clone()),
+            Some(rest) if to.is_empty() => Some(rest.to_owned()),
+            Some(rest) => Some(format!("{}/{}", to, rest)),
+            None => None,
+        }
+    })
+}
+
+/// Iterate over the ancestors of a path, from the nearest one.
+fn parents(path: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(path.rsplit_once('/').map(|(p, _)| p), |p| {
+        p.rsplit_once('/').map(|(p, _)| p)
+    })
+}
+
+fn walk(root: PathBuf, hash: bool, options: &TreeOptions) -> Result<Vec<Entry>> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("Cannot read the local directory {:?}", root))?;
+
+    let mut patterns = GitignoreBuilder::new(&root);
+    for pattern in &options.ignore {
+        patterns.add_line(None, pattern)?;
+    }
+    let patterns = patterns.build()?;
+
+    let walk = ignore::WalkBuilder::new(&root)
+        .follow_links(options.follow_symlinks)
+        .same_file_system(false)
+        .hidden(!options.include_hidden)
+        .git_global(false)
+        .git_ignore(options.use_gitignore)
+        .require_git(false)
+        .add_custom_ignore_filename(NEOCITIES_IGNORE)
+        .filter_entry(move |e| {
+            let is_dir = e.file_type().is_some_and(|t| t.is_dir());
+            !patterns.matched(e.path(), is_dir).is_ignore()
+        })
+        .build();
+
+    let mut tree: Vec<_> = walk
+        .into_iter()
+        .map(|e| e.map_err(describe_walk_error))
+        .filter_ok(|e| options.follow_symlinks || !e.path_is_symlink())
+        .map(|e| Entry::local(&root, &e?, hash, options))
+        .filter_ok(|e| !e.path.is_empty())
+        .filter_ok(|e| !e.local_path.as_ref().unwrap().ends_with(NEOCITIES_IGNORE))
+        .try_collect()?;
+
+    check_paths(&tree)?;
+    tree.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(tree)
+}
+
+/// Fail if any path of a tree is unsafe to upload, as told by [`path_problem`].
+fn check_paths(tree: &[Entry]) -> Result<()> {
+    let unsafe_paths: Vec<_> = tree
+        .iter()
+        .filter_map(|e| Some(format!("  {:?}: {}", e.path, path_problem(&e.path)?)))
+        .collect();
+    if !unsafe_paths.is_empty() {
+        bail!(
+            "{} local path(s) cannot be safely uploaded:\n{}",
+            unsafe_paths.len(),
+            unsafe_paths.join("\n")
+        );
+    }
+    Ok(())
+}
+
+/// Turn errors from walking the tree into friendlier ones.
+fn describe_walk_error(err: ignore::Error) -> anyhow::Error {
+    match err {
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. }
+            if matches!(*err, ignore::Error::Loop { .. }) =>
+        {
+            describe_walk_error(*err)
+        }
+        ignore::Error::Loop { ancestor, child } => anyhow!(
+            "Symbolic link loop: {:?} points to its ancestor {:?}; add it to .neocitiesignore, \
+             or set `follow_symlinks = false` for the site",
+            child,
+            ancestor
+        ),
+        err => err.into(),
+    }
+}
+
+/// Find what, if anything, makes a path unsafe to send to the API, which could reject it, or
+/// store the file somewhere else than intended.
+pub fn path_problem(path: &str) -> Option<&'static str> {
+    if path.starts_with('/') {
+        Some("starts with a slash")
+    } else if path.chars().any(char::is_control) {
+        Some("contains control characters")
+    } else if path.contains('\\') {
+        Some("contains a backslash")
+    } else if path.split('/').any(|c| c == "." || c == "..") {
+        Some("contains a `.` or `..` component")
+    } else if path.split('/').any(str::is_empty) {
+        Some("contains an empty component")
+    } else {
+        None
+    }
+}
+
+/// Restricts a deploy to the paths selected by `--only` and `--exclude` globs.
+pub struct PathFilter {
+    only: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl PathFilter {
+    /// Create a filter from lists of globs. An empty `only` list selects every path.
+    pub fn new(only: &[String], exclude: &[String]) -> Result<Self> {
+        fn build(globs: &[String]) -> Result<GlobSet> {
+            let mut builder = GlobSetBuilder::new();
+            for glob in globs {
+                builder.add(Glob::new(glob)?);
+            }
+            Ok(builder.build()?)
+        }
+        Ok(Self {
+            only: (!only.is_empty()).then(|| build(only)).transpose()?,
+            exclude: build(exclude)?,
+        })
+    }
+
+    /// Test whether a path is selected: it or one of its ancestors must match an `only` glob,
+    /// and neither it nor any of its ancestors may match an `exclude` glob.
+    pub fn is_selected(&self, path: &str) -> bool {
+        let ancestors = path.match_indices('/').map(|(i, _)| &path[..i]);
+        let mut selected = self.only.is_none();
+        for ancestor in ancestors.chain([path]) {
+            if self.exclude.is_match(ancestor) {
+                return false;
+            }
+            selected |= self
+                .only
+                .as_ref()
+                .is_some_and(|only| only.is_match(ancestor));
+        }
+        selected
+    }
+
+    /// Apply the filter to the local and remote trees.
+    ///
+    /// A remote directory is kept only if all the remote entries under it are selected, so that
+    /// deleting it never takes unselected files with it.
+    pub fn apply(&self, local: &mut Vec<Entry>, remote: &mut Vec<Entry>) {
+        local.retain(|e| self.is_selected(&e.path));
+        let (kept, dropped): (Vec<_>, Vec<_>) =
+            remote.drain(..).partition(|e| self.is_selected(&e.path));
+        *remote = kept
+            .into_iter()
+            .filter(|e| {
+                let prefix = format!("{}/", e.path);
+                e.is_file() || !dropped.iter().any(|d| d.path.starts_with(&prefix))
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::assert_equal;
+    use neocities_client::Client;
+
+    const HELLO_SHA1: &str = "943a702d06f34599aee1f8da8ef9f7296031d699";
+    const GOODBYE_SHA1: &str = "fcb7246c878762b3f752a6e1fc8573f154fffdec";
+
+    fn local_tree(root: impl Into<PathBuf>, options: &TreeOptions) -> Result<Vec<Entry>> {
+        root.into().tree(true, options)
+    }
+
+    fn create_local_tree() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+
+        fs::write(root.path().join(NEOCITIES_IGNORE), "ignored").unwrap();
+        fs::write(root.path().join("hello"), "Hello, world!").unwrap();
+        fs::write(root.path().join("hello.txt"), "Hello, world!").unwrap();
+
+        let subdir = root.path().join("subdir");
+        fs::create_dir(&subdir).unwrap();
+        fs::write(subdir.join("goodbye"), "Goodbye, world!").unwrap();
+        fs::write(subdir.join("ignored"), "Ignored").unwrap();
+
+        let empty = root.path().join("empty");
+        fs::create_dir(empty).unwrap();
+
+        root
+    }
+
+    #[test]
+    fn test_local_tree() {
+        let root = create_local_tree();
+        let tree = local_tree(root.path(), &TreeOptions::default()).unwrap();
+        assert_equal(
+            tree.iter().map(|e| &e.path),
+            ["empty", "hello", "hello.txt", "subdir", "subdir/goodbye"],
+        );
+        assert_equal(
+            tree.iter().map(|e| e.info.as_ref().map(|i| i.size)),
+            [None, Some(13), Some(13), None, Some(15)],
+        );
+        assert_equal(
+            tree.iter()
+                .map(|e| e.info.as_ref().map(|i| i.sha1_sum.as_str())),
+            [
+                None,
+                Some(HELLO_SHA1),
+                Some(HELLO_SHA1),
+                None,
+                Some(GOODBYE_SHA1),
+            ],
+        );
+        assert_equal(
+            tree.iter().map(|e| e.local_path.clone().unwrap()),
+            ["empty", "hello", "hello.txt", "subdir", "subdir/goodbye"]
+                .iter()
+                .map(|e| root.path().canonicalize().unwrap().join(e)),
+        );
+        root.close().unwrap();
+    }
+
+    #[test]
+    fn test_local_tree_free_account() {
+        let root = create_local_tree();
+        let mut tree = local_tree(root.path(), &TreeOptions::default()).unwrap();
+        let disallowed =
+            remove_disallowed(&mut tree, |path| Client::has_allowed_extension(true, path));
+        assert_equal(
+            tree.iter().map(|e| e.path.clone()),
+            ["empty", "hello.txt", "subdir"],
+        );
+        assert_equal(
+            disallowed.iter().map(|e| e.path.clone()),
+            ["hello", "subdir/goodbye"],
+        );
+        root.close().unwrap();
+    }
+
+    #[test]
+    fn test_local_tree_normalize_unicode() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("cafe\u{301}.txt"), "").unwrap();
+        let tree = local_tree(root.path(), &TreeOptions::default()).unwrap();
+        assert_equal(tree.iter().map(|e| e.path.as_str()), ["caf\u{e9}.txt"]);
+        let options = TreeOptions {
+            normalize_unicode: false,
+            ..TreeOptions::default()
+        };
+        let tree = local_tree(root.path(), &options).unwrap();
+        assert_equal(tree.iter().map(|e| e.path.as_str()), ["cafe\u{301}.txt"]);
+        root.close().unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_local_tree_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let root = create_local_tree();
+        symlink(root.path().join("hello.txt"), root.path().join("link.txt")).unwrap();
+        let tree = local_tree(root.path(), &TreeOptions::default()).unwrap();
+        assert!(tree.iter().any(|e| e.path == "link.txt"));
+
+        let options = TreeOptions {
+            follow_symlinks: false,
+            ..TreeOptions::default()
+        };
+        let tree = local_tree(root.path(), &options).unwrap();
+        assert!(!tree.iter().any(|e| e.path == "link.txt"));
+
+        symlink(root.path(), root.path().join("subdir/loop")).unwrap();
+        let err = local_tree(root.path(), &TreeOptions::default()).unwrap_err();
+        assert!(err.to_string().starts_with("Symbolic link loop"));
+        assert!(local_tree(root.path(), &options).is_ok());
+        root.close().unwrap();
+    }
+
+    #[test]
+    fn test_local_tree_gitignore() {
+        let root = create_local_tree();
+        fs::write(root.path().join(".gitignore"), "hello.txt\n").unwrap();
+        let tree = local_tree(root.path(), &TreeOptions::default()).unwrap();
+        assert!(tree.iter().any(|e| e.path == "hello.txt"));
+        let options = TreeOptions {
+            use_gitignore: true,
+            ..TreeOptions::default()
+        };
+        let tree = local_tree(root.path(), &options).unwrap();
+        assert!(!tree.iter().any(|e| e.path == "hello.txt"));
+        root.close().unwrap();
+    }
+
+    #[test]
+    fn test_local_tree_ignore() {
+        let root = create_local_tree();
+        let options = TreeOptions {
+            ignore: vec!["*.txt".to_owned(), "subdir/".to_owned()],
+            ..TreeOptions::default()
+        };
+        let tree = local_tree(root.path(), &options).unwrap();
+        assert_equal(tree.iter().map(|e| &e.path), ["empty", "hello"]);
+        root.close().unwrap();
+    }
+
+    #[test]
+    fn test_local_tree_hidden() {
+        let root = create_local_tree();
+        fs::create_dir(root.path().join(".well-known")).unwrap();
+        fs::write(root.path().join(".well-known/security.txt"), "").unwrap();
+        let tree = local_tree(root.path(), &TreeOptions::default()).unwrap();
+        assert!(tree.iter().any(|e| e.path == ".well-known/security.txt"));
+        let options = TreeOptions {
+            include_hidden: false,
+            ..TreeOptions::default()
+        };
+        let tree = local_tree(root.path(), &options).unwrap();
+        assert!(!tree.iter().any(|e| e.path.starts_with(".well-known")));
+        root.close().unwrap();
+    }
+
+    #[test]
+    fn test_merged_tree() {
+        let root = create_local_tree();
+        let extra = tempfile::tempdir().unwrap();
+        fs::create_dir(extra.path().join("img")).unwrap();
+        fs::write(extra.path().join("img/logo.png"), "").unwrap();
+        let sources = [
+            SourceDir {
+                root: root.path().to_owned(),
+                prefix: String::new(),
+            },
+            SourceDir {
+                root: extra.path().to_owned(),
+                prefix: "assets/static".to_owned(),
+            },
+        ];
+        let tree = merged_tree(&sources, true, &TreeOptions::default()).unwrap();
+        let paths: Vec<_> = tree.iter().map(|e| e.path.as_str()).collect();
+        assert!(paths.contains(&"hello.txt"));
+        assert!(paths.contains(&"assets"));
+        assert!(paths.contains(&"assets/static"));
+        assert!(paths.contains(&"assets/static/img/logo.png"));
+        assert!(paths.windows(2).all(|w| w[0] < w[1]));
+
+        // The same directory under the same prefix provides every file twice.
+        let sources = [sources[0].clone(), sources[0].clone()];
+        let err = merged_tree(&sources, false, &TreeOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("\"hello.txt\""));
+        root.close().unwrap();
+        extra.close().unwrap();
+    }
+
+    #[test]
+    fn test_remap() {
+        let map = [
+            ("dist/assets".to_owned(), "static".to_owned()),
+            ("dist".to_owned(), "".to_owned()),
+            ("robots.txt".to_owned(), "meta/robots.txt".to_owned()),
+        ];
+        assert_eq!(
+            remap("dist/assets/a.png", &map).as_deref(),
+            Some("static/a.png")
+        );
+        assert_eq!(remap("dist/assets", &map).as_deref(), Some("static"));
+        assert_eq!(
+            remap("dist/index.html", &map).as_deref(),
+            Some("index.html")
+        );
+        assert_eq!(
+            remap("robots.txt", &map).as_deref(),
+            Some("meta/robots.txt")
+        );
+        assert_eq!(remap("distant.html", &map), None);
+    }
+
+    #[test]
+    fn test_merged_tree_map() {
+        let root = create_local_tree();
+        let options = TreeOptions {
+            map: vec![
+                ("subdir".to_owned(), "a/b".to_owned()),
+                ("hello.txt".to_owned(), "subdir/goodbye".to_owned()),
+            ],
+            ..TreeOptions::default()
+        };
+        let sources = [SourceDir {
+            root: root.path().to_owned(),
+            prefix: String::new(),
+        }];
+        let tree = merged_tree(&sources, true, &options).unwrap();
+        let paths: Vec<_> = tree.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "a",
+                "a/b",
+                "a/b/goodbye",
+                "empty",
+                "hello",
+                "subdir",
+                "subdir/goodbye"
+            ]
+        );
+        assert!(!tree[0].is_file());
+        assert!(tree[6].is_file());
+
+        let options = TreeOptions {
+            map: vec![("hello.txt".to_owned(), "hello".to_owned())],
+            ..TreeOptions::default()
+        };
+        assert!(merged_tree(&sources, true, &options).is_err());
+        root.close().unwrap();
+    }
+
+    #[test]
+    fn test_memory_tree() {
+        let mut memory = MemoryTree::default();
+        memory.add_file("./index.html", &b"<html></html>"[..]);
+        memory.add_file("./.DS_Store", &b""[..]);
+        memory.add_file("./drafts/post.html", &b""[..]);
+        memory.add_file("./img/logo.png", &b""[..]);
+        memory.add_dir("./");
+        memory.add_dir("./drafts/");
+        let options = TreeOptions {
+            ignore: vec!["drafts/".to_owned()],
+            include_hidden: false,
+            ..TreeOptions::default()
+        };
+        let tree = mounted_tree(&[(&memory, "blog")], true, &options).unwrap();
+        let paths: Vec<_> = tree.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["blog", "blog/img", "blog/img/logo.png", "blog/index.html"]
+        );
+        let index = &tree[3];
+        assert_eq!(&index.read().unwrap()[..], b"<html></html>");
+        assert_eq!(
+            index.info.as_ref().unwrap().sha1_sum,
+            "941efb7368e46b27b937d34b07fc4d41da01b002"
+        );
+    }
+
+    #[test]
+    fn test_sha1_sum() {
+        let dir = tempfile::tempdir().unwrap();
+        // Large enough to be memory-mapped with the `mmap` feature.
+        for size in [0, 100, 17 * 1024 * 1024] {
+            let content: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            let path = dir.path().join(format!("{}.bin", size));
+            fs::write(&path, &content).unwrap();
+            assert_eq!(sha1_sum(&path).unwrap(), sha1_bytes(&content));
+        }
+    }
+
+    #[test]
+    fn test_scope_remote() {
+        let entry = |path: &str| Entry {
+            path: path.to_owned(),
+            info: None,
+            local_path: None,
+            content: None,
+        };
+        let mut remote = [
+            "blog",
+            "blog/posts",
+            "blog/posts/a.html",
+            "blogroll.html",
+            "index.html",
+        ]
+        .map(entry)
+        .to_vec();
+        scope_remote(&mut remote, "blog/posts");
+        let paths: Vec<_> = remote.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, ["blog", "blog/posts", "blog/posts/a.html"]);
+    }
+
+    #[test]
+    fn test_empty_remote_dirs() {
+        let entry = |path: &str| Entry {
+            path: path.to_owned(),
+            info: path.contains('.').then(|| FileInfo {
+                size: 0,
+                sha1_sum: String::new(),
+                modified: None,
+            }),
+            local_path: None,
+            content: None,
+        };
+        let remote = [
+            "blog",
+            "blog/old",
+            "blog/old/drafts",
+            "blog/posts",
+            "blog/posts/a.html",
+            "guestbook",
+            "images",
+            "index.html",
+        ]
+        .map(entry)
+        .to_vec();
+        let is_protected = |path: &str| path.starts_with("guestbook");
+        assert_eq!(
+            empty_remote_dirs(&remote, "", is_protected),
+            ["blog/old", "images"]
+        );
+        assert_eq!(
+            empty_remote_dirs(&remote, "blog/old", is_protected),
+            ["blog/old/drafts"]
+        );
+    }
+
+    #[test]
+    fn test_protect_remote() {
+        let entry = |path: &str| Entry {
+            path: path.to_owned(),
+            info: (!path.starts_with("guestbook") || path.contains('.')).then(|| FileInfo {
+                size: 0,
+                sha1_sum: String::new(),
+                modified: None,
+            }),
+            local_path: None,
+            content: None,
+        };
+        let local = ["data.json"].map(entry).to_vec();
+        let mut remote = [
+            "data.json",
+            "guestbook",
+            "guestbook/entries.json",
+            "guestbook/old.html",
+            "old.html",
+        ]
+        .map(entry)
+        .to_vec();
+        let protected = protect_remote(&local, &mut remote, |path| path.ends_with(".json"));
+        assert_eq!(protected, ["guestbook/entries.json"]);
+        let paths: Vec<_> = remote.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, ["data.json", "guestbook/old.html", "old.html"]);
+    }
+
+    #[test]
+    fn test_path_problem() {
+        assert_eq!(path_problem("blog/a..b.html"), None);
+        assert!(path_problem("/index.html").is_some());
+        assert!(path_problem("blog/../index.html").is_some());
+        assert!(path_problem("blog//index.html").is_some());
+        assert!(path_problem("new\nline.html").is_some());
+        assert!(path_problem("back\\slash.html").is_some());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_local_tree_unsafe_path() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("tab\there.html"), "").unwrap();
+        let err = local_tree(root.path(), &TreeOptions::default()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("\"tab\\there.html\": contains control characters"));
+        root.close().unwrap();
+    }
+
+    #[test]
+    fn test_path_filter() {
+        let only = ["blog/**".to_owned(), "index.html".to_owned()];
+        let exclude = ["*.psd".to_owned(), "blog/drafts".to_owned()];
+        let filter = PathFilter::new(&only, &exclude).unwrap();
+        assert!(filter.is_selected("index.html"));
+        assert!(filter.is_selected("blog/post.html"));
+        assert!(!filter.is_selected("blog"));
+        assert!(!filter.is_selected("about.html"));
+        assert!(!filter.is_selected("blog/images/cover.psd"));
+        assert!(!filter.is_selected("blog/drafts/post.html"));
+
+        let filter = PathFilter::new(&[], &exclude).unwrap();
+        assert!(filter.is_selected("about.html"));
+        assert!(!filter.is_selected("cover.psd"));
+    }
+
+    #[test]
+    fn test_path_filter_apply() {
+        let dir = |path: &str| Entry {
+            path: path.to_owned(),
+            info: None,
+            local_path: None,
+            content: None,
+        };
+        let file = |path: &str| Entry {
+            info: Some(FileInfo {
+                size: 0,
+                sha1_sum: String::new(),
+                modified: None,
+            }),
+            ..dir(path)
+        };
+        let filter = PathFilter::new(&[], &["*.psd".to_owned()]).unwrap();
+        let mut local = vec![file("a.psd"), file("b.html")];
+        let mut remote = vec![
+            dir("art"),
+            file("art/c.psd"),
+            dir("old"),
+            file("old/d.html"),
+        ];
+        filter.apply(&mut local, &mut remote);
+        assert_equal(local.iter().map(|e| &e.path), ["b.html"]);
+        assert_equal(remote.iter().map(|e| &e.path), ["old", "old/d.html"]);
+    }
+}
@@ -0,0 +1,150 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Reading the files to deploy from a tar or zip archive.
+
+use crate::trees::MemoryTree;
+use anyhow::{anyhow, Context, Result};
+use flate2::read::GzDecoder;
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+
+/// Read an archive from a file, or from the standard input if the path is `-`, into a tree.
+///
+/// Both zip and tar archives are accepted, the latter optionally compressed with gzip; the
+/// format is told by the contents, not by the file name.
+pub fn read(path: &Path) -> Result<MemoryTree> {
+    let data = if path == Path::new("-") {
+        let mut data = Vec::new();
+        io::stdin().read_to_end(&mut data)?;
+        data
+    } else {
+        fs::read(path)?
+    };
+    from_bytes(&data).with_context(|| format!("Failed to read archive {:?}", path))
+}
+
+/// Read an archive from its bytes into a tree.
+pub fn from_bytes(data: &[u8]) -> Result<MemoryTree> {
+    match data {
+        [b'P', b'K', 3, 4, ..] | [b'P', b'K', 5, 6, ..] => read_zip(data),
+        [0x1f, 0x8b, ..] => read_tar(GzDecoder::new(data)),
+        _ => read_tar(data),
+    }
+}
+
+fn read_tar(reader: impl Read) -> Result<MemoryTree> {
+    let mut tree = MemoryTree::default();
+    for entry in tar::Archive::new(reader).entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?;
+        let path = path
+            .to_str()
+            .ok_or_else(|| anyhow!("Non-UTF-8 path in archive: {:?}", path))?
+            .to_owned();
+        let kind = entry.header().entry_type();
+        if kind.is_dir() {
+            tree.add_dir(path);
+        } else if kind.is_file() || kind.is_contiguous() {
+            let mut content = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut content)?;
+            tree.add_file(path, content);
+        } else {
+            tracing::warn!(
+                "Skipping {:?} in archive, which is not a regular file",
+                path
+            );
+        }
+    }
+    Ok(tree)
+}
+
+fn read_zip(data: &[u8]) -> Result<MemoryTree> {
+    let mut tree = MemoryTree::default();
+    let mut zip = zip::ZipArchive::new(Cursor::new(data))?;
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        let path = file.name().to_owned();
+        if file.is_dir() {
+            tree.add_dir(path);
+        } else {
+            let mut content = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut content)?;
+            tree.add_file(path, content);
+        }
+    }
+    Ok(tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn files(tree: &MemoryTree) -> Vec<(&str, &[u8])> {
+        tree.files
+            .iter()
+            .map(|(path, content)| (path.as_str(), &content[..]))
+            .collect()
+    }
+
+    fn tar() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_size(0);
+        builder
+            .append_data(&mut header, "blog/", io::empty())
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        builder
+            .append_data(&mut header, "blog/a.html", &b"hello"[..])
+            .unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_tar() {
+        let archive = from_bytes(&tar()).unwrap();
+        assert_eq!(Vec::from_iter(&archive.dirs), ["blog/"]);
+        assert_eq!(files(&archive), [("blog/a.html", &b"hello"[..])]);
+    }
+
+    #[test]
+    fn test_tar_gz() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        encoder.write_all(&tar()).unwrap();
+        let archive = from_bytes(&encoder.finish().unwrap()).unwrap();
+        assert_eq!(files(&archive), [("blog/a.html", &b"hello"[..])]);
+    }
+
+    #[test]
+    fn test_zip() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::FileOptions::default();
+        writer.add_directory("blog/", options).unwrap();
+        writer.start_file("blog/a.html", options).unwrap();
+        writer.write_all(b"hello").unwrap();
+        let data = writer.finish().unwrap().into_inner();
+        let archive = from_bytes(&data).unwrap();
+        assert_eq!(Vec::from_iter(&archive.dirs), ["blog/"]);
+        assert_eq!(files(&archive), [("blog/a.html", &b"hello"[..])]);
+    }
+}
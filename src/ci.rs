@@ -0,0 +1,75 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Non-interactive mode, for continuous integration.
+
+use anyhow::{bail, Result};
+use std::fmt::Display;
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether the non-interactive mode is on.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn the non-interactive mode on or off.
+pub fn init(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether the non-interactive mode is on.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Whether questions can be asked: only outside of the non-interactive mode, and when the
+/// standard input is a terminal.
+pub fn interactive() -> bool {
+    !enabled() && io::stdin().is_terminal()
+}
+
+/// Fail unless questions can be asked, explaining what would have been asked.
+pub fn ensure_interactive(question: &str) -> Result<()> {
+    if !interactive() {
+        bail!("Cannot ask for {} when not running interactively", question);
+    }
+    Ok(())
+}
+
+/// Report an error as a GitHub Actions annotation, on the standard output.
+pub fn annotate(message: impl Display) {
+    println!("::error::{}", escape(&message.to_string()));
+}
+
+/// Escape a message for a workflow command, which must fit in a line.
+fn escape(message: &str) -> String {
+    message
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("100% done"), "100%25 done");
+        assert_eq!(escape("first\r\nsecond"), "first%0D%0Asecond");
+    }
+}
@@ -0,0 +1,120 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Telling free accounts from paid ones, since free accounts cannot upload every kind of file.
+
+use super::config::sites_table;
+use crate::params::{Config, Params, PlanningArgs, Site};
+use anyhow::{anyhow, Result};
+use bytesize::ByteSize;
+use neocities_client::{Client, Error, ErrorKind};
+use std::borrow::Cow;
+use toml_edit::value;
+
+/// Storage quota of free accounts.
+const FREE_ACCOUNT_QUOTA: ByteSize = ByteSize::gb(1);
+
+/// Storage quota of supporter accounts.
+const SUPPORTER_ACCOUNT_QUOTA: ByteSize = ByteSize::gb(50);
+
+/// Largest file free accounts can upload.
+const FREE_ACCOUNT_FILE_LIMIT: ByteSize = ByteSize::mb(10);
+
+/// Largest file supporter accounts can upload.
+const SUPPORTER_ACCOUNT_FILE_LIMIT: ByteSize = ByteSize::mb(100);
+
+/// File uploaded to find out whether an account is free: free accounts cannot upload files with
+/// its extension. It is deleted right away when the upload succeeds.
+const PROBE_FILE: &str = "neocities-deploy-probe.log";
+
+/// Storage quota and largest file of the account of a site, with the name of its type.
+///
+/// An account of unknown type is taken to be a supporter account, as when choosing the files to
+/// upload.
+pub fn limits(site: &Site) -> (ByteSize, ByteSize, &'static str) {
+    if site.free_account.unwrap_or_default() {
+        (FREE_ACCOUNT_QUOTA, FREE_ACCOUNT_FILE_LIMIT, "free")
+    } else {
+        (
+            SUPPORTER_ACCOUNT_QUOTA,
+            SUPPORTER_ACCOUNT_FILE_LIMIT,
+            "supporter",
+        )
+    }
+}
+
+/// Find out whether the account of a site is free, by uploading a file that free accounts cannot
+/// upload.
+pub fn detect(client: &Client) -> Result<bool> {
+    match client.upload(&[(PROBE_FILE, b"neocities-deploy")]) {
+        Ok(()) => {
+            client.delete(&[PROBE_FILE])?;
+            Ok(false)
+        }
+        Err(Error::Api {
+            kind: ErrorKind::InvalidFileType,
+            ..
+        }) => Ok(true),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Settle whether the account of a site is free before deploying it, when the site doesn’t say
+/// (in its `free_account` key, or with `--assume-free` or `--assume-paid`) and `--detect-account`
+/// is given. Otherwise, the account is taken to be a supporter one.
+///
+/// The account type is then detected, and saved to the config file for the next deploys.
+pub fn settle<'a>(
+    params: &Params,
+    name: &str,
+    site: &'a Site,
+    client: &Client,
+    args: &PlanningArgs,
+) -> Result<Cow<'a, Site>> {
+    if site.free_account.is_some() || !args.detect_account {
+        return Ok(Cow::Borrowed(site));
+    }
+    tracing::info!("Finding out whether the account of site {} is free", name);
+    let free_account = detect(client)?;
+    tracing::info!(
+        "The account of site {} is {}",
+        name,
+        if free_account { "free" } else { "paid" }
+    );
+    if let Err(e) = save(params, name, free_account) {
+        tracing::warn!("Failed to save the account type of site {}: {:#}", name, e);
+    }
+    Ok(Cow::Owned(Site {
+        free_account: Some(free_account),
+        ..site.clone()
+    }))
+}
+
+/// Save the account type of a site to its `free_account` key in the config file. A staging site
+/// (`NAME:staging`) has no entry of its own, and shares the key of its site.
+pub fn save(params: &Params, name: &str, free_account: bool) -> Result<()> {
+    let name = name.strip_suffix(":staging").unwrap_or(name);
+    Config::edit(params.config_file(), |document| {
+        let site = sites_table(document)?
+            .get_mut(name)
+            .and_then(|site| site.as_table_like_mut())
+            .ok_or_else(|| anyhow!("Site not found: {}", name))?;
+        site.insert("free_account", value(free_account));
+        Ok(())
+    })
+}
@@ -0,0 +1,74 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+use super::deploy::execute_actions;
+use super::lock::SiteLock;
+use super::plan::PlanFile;
+use super::preflight;
+use crate::control::Control;
+use crate::params::{ApplyArgs, Params};
+use crate::plan::Action;
+use crate::trees;
+use anyhow::{bail, Result};
+
+/// Execute a plan saved by the `plan` command.
+pub fn apply(params: &Params, args: &ApplyArgs, control: &Control) -> Result<()> {
+    let plan = PlanFile::load(&args.file)?;
+    let config = params.config()?;
+    let sites = plan
+        .sites
+        .into_iter()
+        .map(|(name, actions)| {
+            let site = params.site(&config, &name)?;
+            let mut actions = actions;
+            preflight::restore_content(&site, &mut actions)?;
+            check_unchanged(&actions)?;
+            Ok((name, site, actions))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for (name, site, actions) in sites {
+        tracing::info!("Applying plan to site: {}", name);
+        let _lock = SiteLock::acquire(&name)?;
+        let client = site.build_client()?;
+        execute_actions(params, &args.exec, &name, &site, &client, actions, control)?;
+    }
+    tracing::info!("Deployment complete");
+    Ok(())
+}
+
+/// Make sure the files to upload have not changed since the plan was made, so that what is
+/// deployed is what was reviewed.
+fn check_unchanged(actions: &[Action]) -> Result<()> {
+    for action in actions {
+        let Action::Upload(entry) = action else {
+            continue;
+        };
+        let (Some(info), Some(_)) = (&entry.info, &entry.local_path) else {
+            continue;
+        };
+        // Plans made with `--since` do not hash the files.
+        if info.sha1_sum.is_empty() {
+            continue;
+        }
+        // The contents to upload, as rewritten by the deploy pipeline, not only the local file.
+        if trees::sha1_bytes(&entry.read()?) != info.sha1_sum {
+            bail!("File {} has changed since the plan was made", entry.path);
+        }
+    }
+    Ok(())
+}
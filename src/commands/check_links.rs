@@ -0,0 +1,317 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Checking that the links between local HTML files resolve.
+
+use crate::params::Params;
+use crate::trees::{self, Entry};
+use anyhow::{bail, Result};
+use parse_display::Display;
+use percent_encoding::percent_decode_str;
+use std::collections::HashSet;
+
+/// Attributes holding links or references to assets.
+const LINK_ATTRIBUTES: &[&str] = &["href", "src", "srcset", "poster"];
+
+/// Check that the relative links of the local HTML files of the site(s) point to local files.
+pub fn check_links(params: &Params) -> Result<()> {
+    let mut broken = 0;
+    for (name, site) in params.sites()? {
+        println!("Checking links of site {}", name);
+        let local = trees::merged_tree(&site.source_dirs(), false, &site.tree_options())?;
+        let links = broken_links(&local)?;
+        for link in &links {
+            println!("{}", link);
+        }
+        broken += links.len();
+    }
+    if broken > 0 {
+        bail!("{} broken link(s) found", broken);
+    }
+    println!("All links resolve");
+    Ok(())
+}
+
+/// A link from an HTML file to a path that is not in the tree.
+#[derive(Clone, Debug, PartialEq, Display)]
+#[display("{file}: {link}")]
+pub struct BrokenLink {
+    /// Path of the HTML file.
+    pub file: String,
+    /// The link, as written.
+    pub link: String,
+}
+
+/// Find the relative links (and root-relative ones, starting with `/`) of the HTML files of a
+/// tree that point to nothing in it.
+///
+/// Like Neocities, a link to `about` is served by `about.html`, and a link to a directory by its
+/// `index.html`.
+pub fn broken_links(tree: &[Entry]) -> Result<Vec<BrokenLink>> {
+    let paths: HashSet<&str> = tree.iter().map(|entry| entry.path.as_str()).collect();
+    let resolves = |path: &str| {
+        let dir = path.trim_end_matches('/');
+        paths.contains(path)
+            || paths.contains(format!("{}.html", path).as_str())
+            || paths.contains(format!("{}/index.html", dir).as_str())
+            || (dir.is_empty() && paths.contains("index.html"))
+    };
+    let mut broken = Vec::new();
+    for entry in tree
+        .iter()
+        .filter(|entry| entry.is_file() && is_html(&entry.path))
+    {
+        let html = entry.read()?;
+        for link in links(&String::from_utf8_lossy(&html)) {
+            let link = link.value();
+            let ok = match resolve(&entry.path, &link) {
+                Some(Some(path)) => resolves(&path),
+                // Links to other sites, fragments, *&c.
+                Some(None) => true,
+                None => false,
+            };
+            if !ok {
+                broken.push(BrokenLink {
+                    file: entry.path.clone(),
+                    link,
+                });
+            }
+        }
+    }
+    Ok(broken)
+}
+
+/// A link found in a document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Link<'a> {
+    /// Offset of the link in the document, in bytes.
+    pub offset: usize,
+    /// The link, as written in the document.
+    pub raw: &'a str,
+}
+
+impl Link<'_> {
+    /// The link, with character references to `&` decoded.
+    pub fn value(&self) -> String {
+        self.raw.replace("&amp;", "&")
+    }
+}
+
+/// Whether a path is that of an HTML file.
+pub fn is_html(path: &str) -> bool {
+    let path = path.to_lowercase();
+    path.ends_with(".html") || path.ends_with(".htm")
+}
+
+/// Resolve a link found in a file to a path in the tree. Returns `Some(None)` for links that
+/// point outside the tree (to other sites, or only to a fragment of the page), and `None` for
+/// links that climb above the root.
+pub fn resolve(file: &str, link: &str) -> Option<Option<String>> {
+    let link = link.trim();
+    let is_external = link.starts_with("//")
+        || link.split_once(':').is_some_and(|(scheme, _)| {
+            !scheme.is_empty()
+                && !scheme.contains('/')
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        });
+    let link = link.split(['#', '?']).next().unwrap_or_default();
+    if is_external || link.is_empty() {
+        return Some(None);
+    }
+    let link = percent_decode_str(link).decode_utf8_lossy();
+    let mut components: Vec<&str> = match link.strip_prefix('/') {
+        Some(_) => Vec::new(),
+        None => file.split('/').collect(),
+    };
+    if !link.starts_with('/') {
+        components.pop();
+    }
+    for component in link.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop()?;
+            }
+            component => components.push(component),
+        }
+    }
+    let mut path = components.join("/");
+    if link.ends_with('/') && !path.is_empty() {
+        path.push('/');
+    }
+    Some(Some(path))
+}
+
+/// Extract the links from an HTML document: the values of the [`LINK_ATTRIBUTES`] of its tags,
+/// with each URL of a `srcset`. Comments, and the contents of scripts and style sheets, are
+/// skipped.
+pub fn links<'a>(html: &'a str) -> Vec<Link<'a>> {
+    let link = |raw: &'a str| Link {
+        offset: raw.as_ptr() as usize - html.as_ptr() as usize,
+        raw,
+    };
+    let mut links = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        if let Some(comment) = rest.strip_prefix("!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_lowercase();
+        rest = &rest[name_end..];
+        while let Some((attribute, value, after)) = attribute(rest) {
+            rest = after;
+            let Some(value) = value else {
+                continue;
+            };
+            match attribute.to_lowercase().as_str() {
+                "srcset" => links.extend(
+                    value
+                        .split(',')
+                        .filter_map(|candidate| candidate.split_whitespace().next())
+                        .map(link),
+                ),
+                attribute if LINK_ATTRIBUTES.contains(&attribute) => links.push(link(value)),
+                _ => {}
+            }
+        }
+        rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+        if name == "script" || name == "style" {
+            let close = format!("</{}", name);
+            rest = rest
+                .to_ascii_lowercase()
+                .find(&close)
+                .map_or("", |end| &rest[end..]);
+        }
+    }
+    links
+}
+
+/// Parse the next attribute of a tag, returning its name, its value (if it has one) and the rest
+/// of the tag.
+fn attribute(tag: &str) -> Option<(&str, Option<&str>, &str)> {
+    let tag = tag.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+    if tag.is_empty() || tag.starts_with('>') {
+        return None;
+    }
+    let name_end = tag
+        .find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+        .unwrap_or(tag.len());
+    let (name, rest) = tag.split_at(name_end);
+    let Some(rest) = rest.trim_start().strip_prefix('=') else {
+        return Some((name, None, rest));
+    };
+    let rest = rest.trim_start();
+    let (value, rest) = match rest.chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+            let value = &rest[1..];
+            match value.find(quote) {
+                Some(end) => (&value[..end], &value[end + 1..]),
+                None => (value, ""),
+            }
+        }
+        _ => {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == '>')
+                .unwrap_or(rest.len());
+            rest.split_at(end)
+        }
+    };
+    Some((name, Some(value), rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trees::{MemoryTree, TreeOptions, TreeSource};
+
+    #[test]
+    fn test_links() {
+        let html = r#"<!DOCTYPE html>
+            <link rel=stylesheet href=style.css>
+            <!-- <a href="commented.html"> -->
+            <img src='cat.png' srcset="cat-2x.png 2x, cat-3x.png 3x" alt="A > B">
+            <script>let s = "<a href='script.html'>";</script>
+            <a class="x" href="/about?x=1&amp;y=2#top">About</a>"#;
+        assert_eq!(
+            links(html).iter().map(Link::value).collect::<Vec<_>>(),
+            [
+                "style.css",
+                "cat.png",
+                "cat-2x.png",
+                "cat-3x.png",
+                "/about?x=1&y=2#top"
+            ]
+        );
+        let link = &links("<a href='x.css'>")[0];
+        assert_eq!((link.offset, link.raw), (9, "x.css"));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let some = |path: &str| Some(Some(path.to_owned()));
+        assert_eq!(
+            resolve("blog/post.html", "img/a.png"),
+            some("blog/img/a.png")
+        );
+        assert_eq!(
+            resolve("blog/post.html", "../index.html"),
+            some("index.html")
+        );
+        assert_eq!(resolve("blog/post.html", "/about"), some("about"));
+        assert_eq!(resolve("blog/post.html", "./"), some("blog/"));
+        assert_eq!(resolve("index.html", "a%20b.html"), some("a b.html"));
+        assert_eq!(resolve("index.html", "../up.html"), None);
+        assert_eq!(resolve("index.html", "https://example.com/"), Some(None));
+        assert_eq!(resolve("index.html", "//example.com/x.png"), Some(None));
+        assert_eq!(resolve("index.html", "mailto:me@example.com"), Some(None));
+        assert_eq!(resolve("index.html", "#top"), Some(None));
+    }
+
+    #[test]
+    fn test_broken_links() {
+        let mut tree = MemoryTree::default();
+        tree.add_file(
+            "index.html",
+            &br#"<a href="about">About</a> <a href="blog/">Blog</a> <img src="missing.png">"#[..],
+        );
+        tree.add_file("about.html", &b"<a href='/'>Home</a>"[..]);
+        tree.add_dir("blog");
+        tree.add_file("blog/index.html", &b"<a href='../gone.html'>Gone</a>"[..]);
+        let local = tree.tree(false, &TreeOptions::default()).unwrap();
+        assert_eq!(
+            broken_links(&local).unwrap(),
+            [
+                BrokenLink {
+                    file: "blog/index.html".to_owned(),
+                    link: "../gone.html".to_owned(),
+                },
+                BrokenLink {
+                    file: "index.html".to_owned(),
+                    link: "missing.png".to_owned(),
+                },
+            ]
+        );
+    }
+}
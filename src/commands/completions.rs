@@ -0,0 +1,102 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+use crate::encryption;
+use crate::params::{CompleteNames, CompletionsArgs, Params, COMPLETE_COMMAND};
+use anyhow::Result;
+use clap::{builder::PossibleValuesParser, CommandFactory};
+use clap_complete::Shell;
+use std::{env, fs};
+
+/// Placeholders given as the only possible values of `--site` and `--group` when generating the
+/// script, then replaced with a call to the `__complete` subcommand.
+const SITES: &str = "__neocities_deploy_sites__";
+const GROUPS: &str = "__neocities_deploy_groups__";
+
+/// Print a shell completion script.
+///
+/// In Bash, Zsh and Fish, the names of the sites and groups are completed after `--site` and
+/// `--group` by running `neocities-deploy __complete`, so that they are always those of the
+/// configuration file in use. (The scripts for Elvish and PowerShell do not complete the values
+/// of options.)
+pub fn completions(args: &CompletionsArgs) -> Result<()> {
+    let mut command = Params::command();
+    let dynamic = matches!(args.shell, Shell::Bash | Shell::Zsh | Shell::Fish);
+    if dynamic {
+        command = command
+            .mut_arg("sites", |arg| {
+                arg.value_parser(PossibleValuesParser::new([SITES]))
+            })
+            .mut_arg("groups", |arg| {
+                arg.value_parser(PossibleValuesParser::new([GROUPS]))
+            });
+    }
+    let mut script = Vec::new();
+    clap_complete::generate(
+        args.shell,
+        &mut command,
+        env!("CARGO_PKG_NAME"),
+        &mut script,
+    );
+    let mut script = String::from_utf8(script)?;
+    if dynamic {
+        for (placeholder, names) in [(SITES, "sites"), (GROUPS, "groups")] {
+            script = script.replace(placeholder, &call(args.shell, names));
+        }
+    }
+    print!("{}", script);
+    Ok(())
+}
+
+/// Shell code that lists the names of the configured sites or groups, in place of a list of
+/// possible values in the script generated for a shell.
+fn call(shell: Shell, names: &str) -> String {
+    let command = format!(
+        "{} {} {} 2>/dev/null",
+        env!("CARGO_PKG_NAME"),
+        COMPLETE_COMMAND,
+        names
+    );
+    match shell {
+        Shell::Fish => format!("({})", command),
+        _ => format!("$({})", command),
+    }
+}
+
+/// Print the names of the configured sites or groups, one per line.
+///
+/// Nothing is printed if there is no configuration file, or if it is encrypted and the passphrase
+/// is not in the environment, so that completing never asks for it.
+pub fn complete(params: &Params, names: CompleteNames) -> Result<()> {
+    let path = params.config_file();
+    let Ok(contents) = fs::read(&path) else {
+        return Ok(());
+    };
+    if encryption::is_encrypted(&contents) && env::var(encryption::PASSPHRASE_VAR).is_err() {
+        return Ok(());
+    }
+    let config = params.config()?;
+    let names: Vec<&String> = match names {
+        CompleteNames::Sites => config.sites.keys().collect(),
+        CompleteNames::Groups => config.groups.keys().collect(),
+    };
+    for name in names {
+        println!("{}", name);
+    }
+    Ok(())
+}
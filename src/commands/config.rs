@@ -16,19 +16,324 @@
 ////////       You should have received a copy of the GNU General Public License           ////////
 ////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
 
-use crate::params::{Config, Params, Site};
-use anyhow::Result;
+use super::history::Record;
+use super::journal::Journal;
+use super::lock::SiteLock;
+use super::snapshot::Snapshot;
+use super::{listing, rollback};
+use crate::params::{
+    redact_url, Config, ConfigAction, ConfigArgs, ConfigFormat, LoginArgs, Params, Site,
+};
+use crate::{ci, encryption, hooks};
+use anyhow::{anyhow, bail, Result};
+use indexmap::IndexMap;
 use inquire::validator::{ErrorMessage, Validation};
 use neocities_client::ureq;
 use neocities_client::Auth;
+use serde::{Deserialize, Serialize};
+use std::io;
 use std::path::{Path, PathBuf};
+use std::{env, fs, process};
+use toml_edit::{DocumentMut, TableLike};
 use url::Url;
 
+/// Configure a site, interactively or with options, or manage the configured sites.
+pub fn config(params: &Params, args: &ConfigArgs) -> Result<()> {
+    match &args.action {
+        None if args.non_interactive() => non_interactive(params, args),
+        None => interactive(params),
+        Some(ConfigAction::Remove { name }) => remove(params, name),
+        Some(ConfigAction::Rename { old, new }) => rename(params, old, new),
+        Some(ConfigAction::Encrypt) => encrypt(params),
+        Some(ConfigAction::Decrypt) => decrypt(params),
+        Some(ConfigAction::Show { json }) => show(params, *json),
+        Some(ConfigAction::Edit) => edit(params),
+        Some(ConfigAction::Import { name, path, .. }) => import_gem(params, name, path),
+    }
+}
+
+/// Edit the configuration file with the user’s editor.
+///
+/// The file is edited as a temporary copy (decrypted, if it is encrypted), which replaces it only
+/// if it makes a valid configuration. Otherwise, the user may edit it again, or give up and leave
+/// the configuration file as it was.
+fn edit(params: &Params) -> Result<()> {
+    let path = params.config_file();
+    let original = match path.exists() {
+        true => Config::read(&path)?,
+        false => String::new(),
+    };
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    let copy = env::temp_dir().join(format!("neocities-deploy-{}.{}", process::id(), extension));
+    fs::write(&copy, &original)?;
+    let result = edit_copy(&path, &copy, &original);
+    fs::remove_file(&copy)?;
+    result
+}
+
+/// Run the editor on the copy of the configuration file until it is valid, then save it.
+fn edit_copy(path: &Path, copy: &Path, original: &str) -> Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| if cfg!(windows) { "notepad" } else { "vi" }.to_owned());
+    let command = match cfg!(windows) {
+        true => format!("{} \"%NEOCITIES_DEPLOY_EDIT_FILE%\"", editor),
+        false => format!("{} \"$NEOCITIES_DEPLOY_EDIT_FILE\"", editor),
+    };
+    let envs = [("NEOCITIES_DEPLOY_EDIT_FILE", copy.display().to_string())];
+    loop {
+        hooks::run(&command, None, &envs)?;
+        let contents = fs::read_to_string(copy)?;
+        if contents == original {
+            eprintln!("No changes made");
+            return Ok(());
+        }
+        match Config::validate(path, &contents) {
+            Ok(()) => {
+                Config::write(path, &contents)?;
+                eprintln!("Configuration saved to {:?}", path);
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("{:#}", e);
+                let again = ci::interactive()
+                    && inquire::Confirm::new("Edit the file again?")
+                        .with_default(true)
+                        .prompt()?;
+                if !again {
+                    bail!("Configuration is invalid; {:?} was left unchanged", path);
+                }
+            }
+        }
+    }
+}
+
+/// Add the site whose API key is stored by the official Neocities CLI, the `neocities` gem.
+fn import_gem(params: &Params, name: &Option<String>, path: &Option<String>) -> Result<()> {
+    let gem_config = directories::BaseDirs::new()
+        .ok_or_else(|| anyhow!("Could not find the home directory"))?
+        .home_dir()
+        .join(".config/neocities/config");
+    let (key, gem_sitename) = read_gem_config(&gem_config)?;
+    let (sitename, mut site) = site_from_key(key, None, params.api_url.clone())?;
+    let name = name.clone().or(gem_sitename).unwrap_or(sitename);
+    site.path = match path {
+        Some(path) => path.clone(),
+        None => env::current_dir()?.to_string_lossy().into_owned(),
+    };
+
+    let config_file = params.config_file();
+    let mut config = match config_file.exists() {
+        true => Config::load_file(&config_file)?,
+        false => Config::default(),
+    };
+    if config.has_site(&name) {
+        bail!("Site already exists: {}", name);
+    }
+    config.insert_site(name.clone(), site);
+    config.save(&config_file)?;
+    eprintln!("Site {} imported from {:?}", name, gem_config);
+    Ok(())
+}
+
+/// Read the API key, and the site name if there is one, from the configuration file of the
+/// `neocities` gem. Recent versions of the gem store them as JSON; older ones store only the key.
+fn read_gem_config(path: &Path) -> Result<(String, Option<String>)> {
+    #[derive(Deserialize)]
+    struct GemConfig {
+        #[serde(rename = "API_KEY")]
+        api_key: String,
+        #[serde(rename = "SITENAME")]
+        sitename: Option<String>,
+    }
+
+    let contents = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Could not read the neocities gem config {:?}: {}", path, e))?;
+    let (key, sitename) = match serde_json::from_str::<GemConfig>(&contents) {
+        Ok(config) => (config.api_key, config.sitename),
+        Err(_) => (contents, None),
+    };
+    let key = key.trim();
+    if key.is_empty() {
+        bail!("No API key in the neocities gem config {:?}", path);
+    }
+    Ok((key.to_owned(), sitename))
+}
+
+/// Show the settings that apply to the selected sites: those of the configuration file and the
+/// files it includes, with the global settings applied and defaults filled in. Passwords and API
+/// keys are redacted.
+fn show(params: &Params, json: bool) -> Result<()> {
+    #[derive(Serialize)]
+    struct Effective {
+        site: IndexMap<String, Site>,
+    }
+
+    eprintln!("Configuration file: {:?}", params.config_file());
+    let site = params
+        .sites()?
+        .into_iter()
+        .map(|(name, site)| {
+            let mut site = site.with_defaults();
+            site.auth = site.auth.as_ref().map(redact);
+            site.proxy = site.proxy.as_deref().map(redact_url);
+            site.proxy_pass = site.proxy_pass.as_ref().map(|_| REDACTED.to_owned());
+            if let Some(staging) = &mut site.staging {
+                staging.auth = redact(&staging.auth);
+                staging.proxy = staging.proxy.as_deref().map(redact_url);
+            }
+            (name, site)
+        })
+        .collect();
+    let format = if json {
+        ConfigFormat::Json
+    } else {
+        ConfigFormat::Toml
+    };
+    print!("{}", format.to_string(&Effective { site })?);
+    Ok(())
+}
+
+/// Replacement for the secrets in the output of `config show`.
+const REDACTED: &str = "********";
+
+/// Hide the secret part of the credentials of a site, keeping the username.
+fn redact(auth: &Auth) -> Auth {
+    match auth {
+        Auth::Credentials(username, _) => Auth::Credentials(username.clone(), REDACTED.to_owned()),
+        Auth::ApiKey(_) => Auth::ApiKey(REDACTED.to_owned()),
+    }
+}
+
+/// Encrypt the configuration file. It is decrypted transparently whenever it is loaded.
+fn encrypt(params: &Params) -> Result<()> {
+    let path = params.config_file();
+    let contents = fs::read(&path)?;
+    if encryption::is_encrypted(&contents) {
+        bail!("The configuration file is already encrypted");
+    }
+    let contents = String::from_utf8(contents)?;
+    let table = ConfigFormat::of(&path).parse_table(&contents)?;
+    Config::from_table(table, &[(path.clone(), contents.clone())])?;
+    fs::write(&path, encryption::encrypt(&contents)?)?;
+    eprintln!("Configuration file {:?} encrypted", path);
+    Ok(())
+}
+
+/// Decrypt the configuration file.
+fn decrypt(params: &Params) -> Result<()> {
+    let path = params.config_file();
+    if !encryption::is_encrypted(&fs::read(&path)?) {
+        bail!("The configuration file is not encrypted");
+    }
+    fs::write(&path, Config::read(&path)?)?;
+    eprintln!("Configuration file {:?} decrypted", path);
+    Ok(())
+}
+
+/// Remove a site from the configuration file.
+fn remove(params: &Params, name: &str) -> Result<()> {
+    Config::edit(params.config_file(), |document| {
+        sites_table(document)?
+            .remove(name)
+            .ok_or_else(|| anyhow!("Site not found: {}", name))?;
+        Ok(())
+    })?;
+    eprintln!("Site {} removed", name);
+    Ok(())
+}
+
+/// Rename a site in the configuration file, in place, and in the groups it belongs to, then move
+/// the files kept for it in the cache and data directories (its history, stash, *&c.*) to the new
+/// name.
+///
+/// Both names are locked meanwhile, so that a site is never renamed while it is being deployed.
+fn rename(params: &Params, old: &str, new: &str) -> Result<()> {
+    let _old = SiteLock::acquire(old)?;
+    let _new = SiteLock::acquire(new)?;
+    Config::edit(params.config_file(), |document| {
+        let sites = sites_table(document)?;
+        if sites.contains_key(new) {
+            bail!("Site already exists: {}", new);
+        }
+        if !sites.contains_key(old) {
+            bail!("Site not found: {}", old);
+        }
+        rename_key(sites, old, new);
+        let groups = document
+            .get_mut("group")
+            .and_then(|g| g.as_table_like_mut());
+        for (_, group) in groups.into_iter().flat_map(|groups| groups.iter_mut()) {
+            let members = group.get_mut("sites").and_then(|s| s.as_array_mut());
+            for member in members.into_iter().flat_map(|members| members.iter_mut()) {
+                if member.as_str() == Some(old) {
+                    let decor = member.decor().clone();
+                    *member = new.into();
+                    *member.decor_mut() = decor;
+                }
+            }
+        }
+        Ok(())
+    })?;
+    let files = |site: &str| {
+        [
+            Journal::file(site),
+            listing::file(site),
+            Record::file(site),
+            Snapshot::file(site),
+            rollback::dir(site),
+        ]
+    };
+    for (from, to) in files(old).iter().zip(&files(new)) {
+        if !from.exists() {
+            continue;
+        }
+        // Files left under the new name belong to a site that is no longer configured.
+        if to.is_dir() {
+            fs::remove_dir_all(to)?;
+        }
+        tracing::debug!("Moving {:?} to {:?}", from, to);
+        fs::rename(from, to)?;
+    }
+    eprintln!("Site {} renamed to {}", old, new);
+    Ok(())
+}
+
+/// Rename a key of a table, keeping its place and the formatting of its entry.
+fn rename_key(table: &mut dyn TableLike, old: &str, new: &str) {
+    let keys: Vec<String> = table.iter().map(|(key, _)| key.to_owned()).collect();
+    let Some(index) = keys.iter().position(|key| key == old) else {
+        return;
+    };
+    // Entries can only be added at the end, so the renamed one and those after it are put back
+    // in order.
+    for key in &keys[index..] {
+        let formatted = table.key(key).cloned();
+        let Some(item) = table.remove(key) else {
+            continue;
+        };
+        let key = if key == old { new } else { key };
+        table.insert(key, item);
+        if let (Some(formatted), Some(mut renamed)) = (formatted, table.key_mut(key)) {
+            *renamed.leaf_decor_mut() = formatted.leaf_decor().clone();
+            *renamed.dotted_decor_mut() = formatted.dotted_decor().clone();
+        }
+    }
+}
+
+/// Get the table of sites from a configuration document.
+pub fn sites_table(document: &mut DocumentMut) -> Result<&mut dyn TableLike> {
+    document
+        .get_mut("site")
+        .and_then(|sites| sites.as_table_like_mut())
+        .ok_or_else(|| anyhow!("No sites in the configuration file"))
+}
+
 /// Configure a site interactively.
-pub fn config(params: &Params) -> Result<()> {
+pub fn interactive(params: &Params) -> Result<()> {
     eprintln!("Configuring sites interactively.");
 
-    let (name, site) = login()?;
+    let (name, site) = login(params)?;
     let (name, site) = other_options(name, site)?;
 
     save_site(params.config_file(), name, site)?;
@@ -36,8 +341,93 @@ pub fn config(params: &Params) -> Result<()> {
     Ok(())
 }
 
+/// Add or update a site with the options given on the command line, without prompts.
+///
+/// A site that already exists keeps the settings that aren’t given. New credentials replace its
+/// old ones.
+fn non_interactive(params: &Params, args: &ConfigArgs) -> Result<()> {
+    let config_file = params.config_file();
+    let mut config = match config_file.exists() {
+        true => Config::load_file(&config_file)?,
+        false => Config::default(),
+    };
+    let login = login_with(params, &args.login)?;
+    let name = match (&args.login.name, &login) {
+        (Some(name), _) => name.clone(),
+        (None, Some((sitename, _))) => sitename.clone(),
+        (None, None) => bail!("The name of the site is required when no credentials are given"),
+    };
+    let mut site = match (config.sites.get(&name), login) {
+        (Some(site), Some((_, new))) => Site {
+            auth: new.auth,
+            auth_cmd: None,
+            ..site.clone()
+        },
+        (Some(site), None) => site.clone(),
+        (None, Some((_, new))) => match &args.path {
+            Some(_) => new,
+            None => bail!("The path of the site is required to add site {}", name),
+        },
+        (None, None) => bail!("Credentials are required to add site {}", name),
+    };
+    if let Some(path) = &args.path {
+        site.path = path.clone();
+    }
+    if args.login.proxy.is_some() {
+        site.proxy = args.login.proxy.clone();
+    }
+    if args.login.free_account.is_some() {
+        site.free_account = args.login.free_account;
+    }
+    let added = !config.has_site(&name);
+    config.insert_site(name.clone(), site);
+    config.save(&config_file)?;
+    match added {
+        true => eprintln!("Site {} added", name),
+        false => eprintln!("Site {} updated", name),
+    }
+    Ok(())
+}
+
+/// Log in with the credentials given on the command line, if any, and build a preliminary
+/// [`Site`] object. A password not given along with the username is read from the standard
+/// input.
+pub fn login_with(params: &Params, args: &LoginArgs) -> Result<Option<(String, Site)>> {
+    let (proxy, api_url) = (args.proxy.clone(), params.api_url.clone());
+    let password = match (&args.username, &args.password) {
+        (Some(_), None) if args.api_key.is_none() => Some(read_password()?),
+        (_, password) => password.clone(),
+    };
+    match (&args.api_key, &args.username, password) {
+        (Some(key), _, _) => site_from_key(key.clone(), proxy, api_url).map(Some),
+        (None, Some(username), Some(password)) => {
+            build_site(username.clone(), password, proxy, api_url).map(Some)
+        }
+        (None, _, _) => Ok(None),
+    }
+}
+
+/// Read the password from the standard input: prompt for it on a terminal, otherwise read the
+/// first line.
+fn read_password() -> Result<String> {
+    if ci::interactive() {
+        return Ok(inquire::Password::new("Password:")
+            .with_display_mode(inquire::PasswordDisplayMode::Masked)
+            .without_confirmation()
+            .prompt()?);
+    }
+    let mut password = String::new();
+    io::stdin().read_line(&mut password)?;
+    let password = password.trim_end_matches(['\r', '\n']);
+    if password.is_empty() {
+        bail!("A password is required along with the username");
+    }
+    Ok(password.to_owned())
+}
+
 /// Prompt the user for the login credentials, log in and build a preliminary [`Site`] object.
-fn login() -> Result<(String, Site)> {
+pub fn login(params: &Params) -> Result<(String, Site)> {
+    ci::ensure_interactive("the login; use --api-key or --username")?;
     let mut username = String::new();
     let mut proxy = String::new();
     loop {
@@ -63,6 +453,7 @@ fn login() -> Result<(String, Site)> {
             username.clone(),
             password,
             (!proxy.is_empty()).then(|| proxy.clone()),
+            params.api_url.clone(),
         ) {
             break Ok((name, site));
         }
@@ -107,8 +498,9 @@ fn other_options(name: String, site: Site) -> Result<(String, Site)> {
 /// * If the configuration file does not exist, it will be created.
 fn save_site(config_file: impl Into<PathBuf>, name: String, site: Site) -> Result<()> {
     let config_file = config_file.into();
-    let mut config = Config::load(&config_file).unwrap_or_else(|_| Default::default());
+    let mut config = Config::load_file(&config_file).unwrap_or_else(|_| Default::default());
     if config.has_site(&name) {
+        ci::ensure_interactive("replacing the existing site")?;
         let replace = inquire::Confirm::new("Site already exists. Replace it?")
             .with_default(false)
             .prompt()?;
@@ -122,20 +514,37 @@ fn save_site(config_file: impl Into<PathBuf>, name: String, site: Site) -> Resul
 }
 
 /// Build a [`Site`] object for the login function.
-fn build_site(username: String, password: String, proxy: Option<String>) -> Result<(String, Site)> {
-    let mut site = Site {
-        auth: Auth::Credentials(username, password),
-        path: "/".to_owned(),
-        free_account: None,
-        proxy: proxy.clone(),
-    };
+///
+/// `api_url` is the base URL of the API, if not the default one.
+pub fn build_site(
+    username: String,
+    password: String,
+    proxy: Option<String>,
+    api_url: Option<String>,
+) -> Result<(String, Site)> {
+    let mut site = Site::new(Auth::Credentials(username, password), "/");
+    site.proxy = proxy;
+    site.api_url = api_url;
     let client = site.build_client()?;
-    site.auth = Auth::ApiKey(client.key()?);
+    site.auth = Some(Auth::ApiKey(client.key()?));
     let client = site.build_client()?;
     let name = client.info()?.sitename;
     Ok((name, site))
 }
 
+/// Build a [`Site`] object from an API key, checking the key by fetching the site name.
+fn site_from_key(
+    key: String,
+    proxy: Option<String>,
+    api_url: Option<String>,
+) -> Result<(String, Site)> {
+    let mut site = Site::new(Auth::ApiKey(key), ".");
+    site.proxy = proxy;
+    site.api_url = api_url;
+    let name = site.build_client()?.info()?.sitename;
+    Ok((name, site))
+}
+
 /// Validate a non-empty string.
 fn non_empty_validator(s: &str) -> Validation {
     if s.is_empty() {
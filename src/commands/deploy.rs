@@ -16,111 +16,705 @@
 ////////       You should have received a copy of the GNU General Public License           ////////
 ////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
 
-use crate::trees::Entry;
-use crate::{params::Params, trees};
-use anyhow::Result;
-use itertools::{EitherOrBoth::*, Itertools};
+use super::account;
+use super::history::Record;
+use super::journal::Journal;
+use super::listing;
+use super::lock::SiteLock;
+use super::metrics::Metrics;
+use super::preflight;
+use super::rollback;
+use super::snapshot;
+use crate::control::Control;
+use crate::executor::{
+    ErrorPolicy, Event, ExecuteOptions, Executor, Progress, Summary, POLICY_RETRIES,
+};
+use crate::params::{DeployArgs, ExecutionArgs, OnError, Params, PlanningArgs, Site};
+use crate::plan::{Action, Compare, Plan};
+use crate::trees;
+use crate::trees::{Entry, MemoryTree, PathFilter};
+use crate::{archive, ci, failures, git, hooks, images, style, tui};
+use anyhow::{anyhow, bail, Result};
+use bytesize::ByteSize;
+use chrono::{SecondsFormat, Utc};
 use neocities_client::Client;
-use parse_display::Display;
-use std::fs;
+use serde_json::json;
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 
 /// Deploy local files to the site(s).
-pub fn deploy(params: &Params) -> Result<()> {
-    let sites = params.sites()?;
+///
+/// With `--jobs-sites`, several sites are deployed at once, each in a thread named after it so
+/// that its log messages are prefixed with its name, and a summary of all of them is shown at
+/// the end.
+///
+/// With `--metrics-file`, the metrics of the deploy of each site are written at the end, whether
+/// it succeeded or not.
+pub fn deploy(params: &Params, args: &DeployArgs, control: &Control) -> Result<()> {
+    let sites = args.sites(params)?;
     if sites.is_empty() {
         eprintln!("No sites to deploy");
         return Ok(());
     }
-    for (name, site) in sites {
-        log::info!("Deploying site: {}", name);
-        let free_account = site.free_account.unwrap_or_default();
-        let local = trees::local_tree(&site.path, free_account)?;
-        let client = site.build_client()?;
-        let list = client.list()?;
-        let remote = trees::remote_tree(&list);
-        for action in Action::make_strategy(local, remote) {
-            action.apply(&client).or_else(|e| {
-                if params.ignore_errors {
-                    log::error!("{}", e);
-                    Ok(())
-                } else {
-                    Err(e)
-                }
-            })?;
+    let archive = args.archive.as_deref().map(archive::read).transpose()?;
+    let metrics = Metrics::default();
+    let result = if args.jobs_sites > 1 && sites.len() > 1 {
+        let results =
+            deploy_concurrently(params, args, &sites, archive.as_ref(), control, &metrics);
+        summarize(&results)
+    } else {
+        sites
+            .iter()
+            .try_for_each(|(name, site)| {
+                metrics
+                    .measure(name, || {
+                        deploy_one(params, args, name, site, archive.as_ref(), control)
+                    })
+                    .map(drop)
+            })
+            .map(|()| tracing::info!("Deployment complete"))
+    };
+    let written = args
+        .metrics_file
+        .as_deref()
+        .map_or(Ok(()), |path| metrics.write(path));
+    result?;
+    written
+}
+
+/// Deploy up to `--jobs-sites` sites at once, returning the name and result of each, in order.
+fn deploy_concurrently(
+    params: &Params,
+    args: &DeployArgs,
+    sites: &[(String, Site)],
+    archive: Option<&MemoryTree>,
+    control: &Control,
+    metrics: &Metrics,
+) -> Vec<(String, Result<Summary>)> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..args.jobs_sites.min(sites.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some((name, site)) = sites.get(index) else {
+                    break;
+                };
+                let result = thread::Builder::new()
+                    .name(name.clone())
+                    .spawn_scoped(scope, || {
+                        metrics.measure(name, || {
+                            deploy_one(params, args, name, site, archive, control)
+                        })
+                    })
+                    .map_err(anyhow::Error::from)
+                    .and_then(|handle| {
+                        handle
+                            .join()
+                            .unwrap_or_else(|_| Err(anyhow!("Deploy of {} panicked", name)))
+                    });
+                results.lock().unwrap().push((index, name.clone(), result));
+            });
         }
+    });
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(index, _, _)| *index);
+    results
+        .into_iter()
+        .map(|(_, name, result)| (name, result))
+        .collect()
+}
+
+/// Show the outcome of each site of a concurrent deploy, failing if any of them failed.
+fn summarize(results: &[(String, Result<Summary>)]) -> Result<()> {
+    let mut failed = 0;
+    for (name, result) in results {
+        match result {
+            Ok(summary) => tracing::info!(
+                "{}: {}, {}, {} failed",
+                name,
+                style::action("upload", format!("{} uploaded", summary.uploaded)),
+                style::action("delete", format!("{} deleted", summary.deleted)),
+                summary.failed
+            ),
+            Err(e) => {
+                failed += 1;
+                tracing::error!("{}: {}", name, e);
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} site(s) failed to deploy", failed, results.len());
     }
-    log::info!("Deployment complete");
+    tracing::info!("Deployment complete");
     Ok(())
 }
 
-#[derive(Clone, Debug, PartialEq, Display)]
-/// Actions to deploy the local tree to the site.
-pub enum Action {
-    /// Upload a file to the remote entry.
-    #[display("upload {0.path}")]
-    Upload(Entry),
-    /// Delete a remote entry.
-    #[display("delete remote {0.path}")]
-    DeleteRemote(Entry),
-}
-
-impl Action {
-    /// Apply the action to the client.
-    fn apply(&self, client: &Client) -> Result<()> {
-        log::info!("Action: {}", self);
-        match self {
-            Action::Upload(entry) => {
-                let local_path = entry.local_path.as_ref().expect("local_path not set");
-                let file = fs::read(local_path)?;
-                client.upload(&[(&entry.path, &file)])?;
-                Ok(())
+/// Deploy a single site, running its hooks and build step.
+fn deploy_one(
+    params: &Params,
+    args: &DeployArgs,
+    name: &str,
+    site: &Site,
+    archive: Option<&MemoryTree>,
+    control: &Control,
+) -> Result<Summary> {
+    let _span = tracing::info_span!("site", site = name).entered();
+    tracing::info!("Deploying site: {}", name);
+    let _lock = SiteLock::acquire(name)?;
+    if archive.is_none() && !preflight::ensure_local_path(name, site, args.create_path)? {
+        return Ok(Summary::default());
+    }
+    let envs = hook_envs(name, site);
+    pre_deploy(name, site, &envs, archive.is_none())?;
+    let pending = if args.resume {
+        Journal::load(name)?
+    } else {
+        None
+    };
+    let result = site.build_client().and_then(|client| {
+        let site = match archive {
+            Some(_) => Cow::Borrowed(site),
+            None => account::settle(params, name, site, &client, &args.plan)?,
+        };
+        let site = site.as_ref();
+        let actions = match pending {
+            Some(mut pending) => {
+                tracing::info!("Resuming deploy, {} action(s) left", pending.len());
+                preflight::restore_content(site, &mut pending)?;
+                pending
             }
-            Action::DeleteRemote(entry) => {
-                client.delete(&[&entry.path])?;
-                Ok(())
+            None if args.mirror => {
+                let local = local_tree(args, site, archive, true)?;
+                plan_mirror(args, site, &client, local)?
             }
+            None => match archive {
+                Some(archive) => {
+                    let hash = args.plan.compare(site).needs_hash();
+                    let local = local_tree(args, site, Some(archive), hash)?;
+                    plan_tree(params, name, &args.plan, site, &client, local)?
+                }
+                None => plan_site(params, name, &args.plan, site, &client)?,
+            },
+        };
+        let exec = &args.exec;
+        execute_actions(params, exec, name, site, &client, actions, control)
+    });
+    post_deploy(site, &envs, &result)?;
+    result
+}
+
+/// Environment variables naming the site to the hooks and the build command.
+pub fn hook_envs(name: &str, site: &Site) -> Vec<(&'static str, String)> {
+    vec![
+        ("NEOCITIES_SITE", name.to_owned()),
+        ("NEOCITIES_SITE_PATH", site.path.clone()),
+    ]
+}
+
+/// Run the `pre_deploy` hook of a site and then, if `build` is set, its build step.
+pub fn pre_deploy(name: &str, site: &Site, envs: &[(&str, String)], build: bool) -> Result<()> {
+    if let Some(pre_deploy) = &site.pre_deploy {
+        hooks::run(pre_deploy, None, envs)?;
+    }
+    if let (Some(command), true) = (&site.build, build) {
+        tracing::info!("Building site: {}", name);
+        hooks::run(&command.command, Some(Path::new(&site.path)), envs)?;
+    }
+    Ok(())
+}
+
+/// Run the `post_deploy` hook of a site, telling it the result of the deploy.
+pub fn post_deploy(site: &Site, envs: &[(&str, String)], result: &Result<Summary>) -> Result<()> {
+    if let Some(post_deploy) = &site.post_deploy {
+        hooks::run(post_deploy, None, &[envs, &result_envs(result)].concat())?;
+    }
+    Ok(())
+}
+
+/// Environment variables exposing the result of a deploy to the `post_deploy` hook.
+fn result_envs(result: &Result<Summary>) -> Vec<(&'static str, String)> {
+    match result {
+        Ok(summary) => vec![
+            ("NEOCITIES_DEPLOY_STATUS", "success".to_owned()),
+            ("NEOCITIES_DEPLOY_UPLOADED", summary.uploaded.to_string()),
+            ("NEOCITIES_DEPLOY_DELETED", summary.deleted.to_string()),
+            ("NEOCITIES_DEPLOY_FAILED", summary.failed.to_string()),
+        ],
+        Err(e) => vec![
+            ("NEOCITIES_DEPLOY_STATUS", "failure".to_owned()),
+            ("NEOCITIES_DEPLOY_ERROR", e.to_string()),
+        ],
+    }
+}
+
+/// Build the local tree to deploy to a site, from an archive if one was given.
+fn local_tree(
+    args: &DeployArgs,
+    site: &Site,
+    archive: Option<&MemoryTree>,
+    hash: bool,
+) -> Result<Vec<Entry>> {
+    let options = args.plan.tree_options(site);
+    match archive {
+        Some(archive) => {
+            let prefix = site.remote_prefix();
+            trees::mounted_tree(&[(archive, &prefix)], hash, &options)
+        }
+        None => trees::merged_tree(&site.source_dirs(), hash, &options),
+    }
+}
+
+/// Compute the actions needed to deploy a site, in the order they should run.
+pub fn plan_site(
+    params: &Params,
+    name: &str,
+    args: &PlanningArgs,
+    site: &Site,
+    client: &Client,
+) -> Result<Vec<Action>> {
+    match &args.since {
+        Some(since) => plan_changes(args, site, since),
+        None => {
+            let hash = args.compare(site).needs_hash();
+            let local = trees::merged_tree(&site.source_dirs(), hash, &args.tree_options(site))?;
+            plan_tree(params, name, args, site, client, local)
+        }
+    }
+}
+
+/// Compute the actions needed to deploy an already built local tree.
+fn plan_tree(
+    params: &Params,
+    name: &str,
+    args: &PlanningArgs,
+    site: &Site,
+    client: &Client,
+    local: Vec<Entry>,
+) -> Result<Vec<Action>> {
+    let filter = PathFilter::new(&args.only, &args.exclude)?;
+    let mut local = local;
+    preflight::prepare_local_tree(&mut local, site, args.strict)?;
+    preflight::check_sizes(&local, site, args)?;
+    preflight::check_collisions(&local)?;
+    preflight::check_links(&local, args)?;
+    let list = listing::list(params, name, site, client)?;
+    let mut remote = trees::remote_tree(&list);
+    trees::scope_remote(&mut remote, &site.remote_prefix());
+    filter.apply(&mut local, &mut remote);
+    for path in trees::protect_remote(&local, &mut remote, site.protection()?) {
+        tracing::debug!("Keeping protected file: {}", path);
+    }
+    let plan = Plan::compare(local, remote, args.compare(site)).reorder(args.order, args.html_last);
+    Ok(plan.actions)
+}
+
+/// Compute the actions that make the site an exact copy of a local tree.
+///
+/// The hashes in the remote listing are not trusted: every remote file is downloaded and hashed
+/// again. Remote files that are not in the local tree are deleted even if they are protected,
+/// but only once the user confirms it (or passes `--yes`).
+fn plan_mirror(
+    args: &DeployArgs,
+    site: &Site,
+    client: &Client,
+    local: Vec<Entry>,
+) -> Result<Vec<Action>> {
+    let mut local = local;
+    preflight::prepare_local_tree(&mut local, site, args.plan.strict)?;
+    preflight::check_sizes(&local, site, &args.plan)?;
+    preflight::check_collisions(&local)?;
+    preflight::check_links(&local, &args.plan)?;
+    let list = client.list()?;
+    let mut remote = trees::remote_tree(&list);
+    trees::scope_remote(&mut remote, &site.remote_prefix());
+    rehash_remote(site, &mut remote)?;
+    let protected = trees::protect_remote(&local, &mut remote.clone(), site.protection()?);
+    if !protected.is_empty() {
+        confirm_protected(&protected, args.yes)?;
+    }
+    let plan = Plan::compare(local, remote, Compare::Hash);
+    Ok(plan.reorder(args.plan.order, args.plan.html_last).actions)
+}
+
+/// Download every remote file, replacing the size and hash from the listing with those of the
+/// actual contents.
+fn rehash_remote(site: &Site, remote: &mut [Entry]) -> Result<()> {
+    let fetch = site.fetcher()?;
+    for entry in remote.iter_mut() {
+        let Some(info) = &mut entry.info else {
+            continue;
+        };
+        tracing::debug!("Checking remote file: {}", entry.path);
+        // A file that cannot be downloaded gets an empty hash, so that it is uploaded again.
+        let (size, sha1_sum) = match fetch(&entry.path)? {
+            Some(content) => (content.len() as u64, trees::sha1_bytes(&content)),
+            None => (0, String::new()),
+        };
+        if info.sha1_sum != sha1_sum {
+            tracing::warn!("Remote listing is out of date for {}", entry.path);
         }
+        info.size = size;
+        info.sha1_sum = sha1_sum;
     }
+    Ok(())
+}
 
-    /// Compare two file trees and create a strategy to deploy them.
-    ///
-    /// **Note:** This function assumes that the two trees are sorted by path. Both `local_tree`
-    /// and `remote_tree` return sorted trees, so this should be a safe assumption.
-    fn make_strategy(local: Vec<Entry>, remote: Vec<Entry>) -> Vec<Action> {
-        use Action::*;
+/// Ask the user to confirm the deletion of protected remote files.
+fn confirm_protected(paths: &[String], yes: bool) -> Result<()> {
+    for path in paths {
+        tracing::warn!("Protected file will be deleted: {}", path);
+    }
+    if yes {
+        return Ok(());
+    }
+    if !ci::interactive() {
+        bail!("Refusing to delete protected files without confirmation; use --yes to allow it");
+    }
+    let message = format!("Delete {} protected file(s)?", paths.len());
+    if !inquire::Confirm::new(&message)
+        .with_default(false)
+        .prompt()?
+    {
+        bail!("Mirror cancelled, nothing was changed");
+    }
+    Ok(())
+}
 
+/// Compute the actions needed to deploy only the files that git reports as changed since a ref,
+/// without listing the files on the site or hashing the local tree.
+fn plan_changes(args: &PlanningArgs, site: &Site, since: &str) -> Result<Vec<Action>> {
+    if site.fingerprint.is_some() {
+        bail!("--since can’t be used with `fingerprint`, which renames the assets that change");
+    }
+    let sources = site.source_dirs();
+    let options = args.tree_options(site);
+    let mut changes = git::Changes::default();
+    for source in &sources {
+        let found = git::changes_since(&source.root, since)?;
+        let remote_path = |path: &String| source.remote_path(path, &options.map);
+        changes
+            .changed
+            .extend(found.changed.iter().map(remote_path));
+        changes
+            .deleted
+            .extend(found.deleted.iter().map(remote_path));
+    }
+    let mut local = trees::merged_tree(&sources, false, &options)?;
+    preflight::prepare_local_tree(&mut local, site, args.strict)?;
+    preflight::check_sizes(&local, site, args)?;
+    preflight::check_collisions(&local)?;
+    preflight::check_links(&local, args)?;
+    let filter = PathFilter::new(&args.only, &args.exclude)?;
+    let is_protected = site.protection()?;
+    // Deletions go first, in case a deleted file is replaced by a directory.
+    let mut actions: Vec<_> = changes
+        .deleted
+        .into_iter()
+        .filter(|path| filter.is_selected(path))
+        .filter(|path| !is_protected(path))
+        .filter(|path| site.is_allowed(path))
+        .map(|path| {
+            Action::DeleteRemote(Entry {
+                path,
+                info: None,
+                local_path: None,
+                content: None,
+            })
+        })
+        .collect();
+    actions.extend(
         local
             .into_iter()
-            .merge_join_by(remote, |a, b| a.path.cmp(&b.path))
-            .flat_map(|pair| match pair {
-                // Local is a file, remote has no entry: upload.
-                Left(l) if l.is_file() => vec![Upload(l)],
-                // Local is a directory, remote has no entry: do nothing.
-                Left(_) => vec![],
-                // Local has no entry, remote is either a file or a directory: delete remote.
-                Right(r) => vec![DeleteRemote(r)],
-                // Now for the cases where we have both local and remote entries:
-                Both(l, r) => match (l.is_file(), r.is_file(), l.is_same(&r)) {
-                    // Remote is a file, local is a directory: delete remote.
-                    (false, true, _) => vec![DeleteRemote(r)],
-                    // Local is a file, remote is a directory: delete remote, upload.
-                    (true, false, _) => vec![DeleteRemote(r), Upload(l)],
-                    // Both are files, but different: upload.
-                    (true, true, false) => vec![Upload(l)],
-                    // Otherwise, do nothing.
-                    _ => vec![],
-                },
+            .filter(|e| e.is_file() && changes.changed.contains(&e.path))
+            .filter(|e| filter.is_selected(&e.path))
+            .map(Action::Upload),
+    );
+    tracing::info!("{} change(s) since {}", actions.len(), since);
+    let plan = Plan::from(actions).reorder(args.order, args.html_last);
+    Ok(plan.actions)
+}
+
+/// Deploy an already built local tree to a site.
+pub fn deploy_site(
+    params: &Params,
+    args: &DeployArgs,
+    name: &str,
+    site: &Site,
+    local: Vec<Entry>,
+    control: &Control,
+) -> Result<Summary> {
+    let client = site.build_client()?;
+    let site = account::settle(params, name, site, &client, &args.plan)?;
+    let actions = plan_tree(params, name, &args.plan, &site, &client, local)?;
+    execute_actions(params, &args.exec, name, &site, &client, actions, control)
+}
+
+/// Execute the actions of a deploy, in order, and summarize the results.
+///
+/// With `--tui`, the actions are reviewed in the terminal UI first, and only those chosen are
+/// run, while the UI shows their progress.
+///
+/// The actions still to be done are kept in a [`Journal`] until the deploy finishes, so that it
+/// can be resumed with `--resume` if it is interrupted. With `--stash`, the remote files the
+/// actions overwrite or delete are saved first, for `rollback`. The snapshot of the site, if any,
+/// is refreshed at the end, after checking the uploaded files with `--verify` and deleting the
+/// remote directories left empty if the site sets `clean_empty_dirs`.
+pub fn execute_actions(
+    params: &Params,
+    args: &ExecutionArgs,
+    name: &str,
+    site: &Site,
+    client: &Client,
+    actions: Vec<Action>,
+    control: &Control,
+) -> Result<Summary> {
+    let actions = match args.tui {
+        true => match tui::review(name, actions)? {
+            Some(actions) => actions,
+            None => bail!("Deploy cancelled, nothing was changed"),
+        },
+        false => actions,
+    };
+    if args.stash || site.stash.unwrap_or_default() {
+        rollback::stash(name, site, client, &actions)?;
+    }
+    let journal = Journal::create(name, &actions)?;
+    if !actions.is_empty() {
+        listing::invalidate(name);
+    }
+    let on_error = site.on_error.as_ref().filter(|_| !params.ignore_errors);
+    let options = ExecuteOptions {
+        jobs: args.jobs.or(site.jobs).unwrap_or(1),
+        batch_files: args.batch_files,
+        batch_bytes: args.batch_bytes,
+        max_memory: Some(args.max_memory),
+        max_retries: args.max_retries,
+        retry_delay: Duration::from_secs(args.retry_delay),
+        on_error: if params.ignore_errors {
+            ErrorPolicy::Continue
+        } else {
+            ErrorPolicy::Abort
+        },
+        on_upload_error: on_error.and_then(OnError::upload),
+        on_delete_error: on_error.and_then(OnError::delete),
+        dry_run: false,
+    };
+    let monitor = match args.tui {
+        true => Some(tui::Monitor::start(name, &actions, control.clone())?),
+        false => None,
+    };
+    let progress = DeployProgress {
+        site: name.to_owned(),
+        json: args.json,
+        journal: &journal,
+        monitor: monitor.as_ref(),
+    };
+    let started_at = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    let started = Instant::now();
+    let result = Executor::new(client, options.clone())
+        .control(control.clone())
+        .progress(&progress)
+        .execute(&actions);
+    if let Some(monitor) = monitor {
+        let shown = match &result {
+            Ok(outcome) if outcome.done < actions.len() => Err(anyhow!("Deployment cancelled")),
+            Ok(outcome) => Ok(outcome.summary(&actions)),
+            Err(e) => Err(anyhow!("{:#}", e)),
+        };
+        monitor.finish(&shown)?;
+    }
+    let duration_ms = started.elapsed().as_millis() as u64;
+    let message = args.message.clone();
+    let record = match &result {
+        Ok(outcome) => Record::new(started_at, duration_ms, message, &actions, outcome),
+        Err(e) => {
+            let pending = journal.pending();
+            let done: Vec<_> = actions
+                .iter()
+                .filter(|a| !pending.contains(a))
+                .cloned()
+                .collect();
+            Record::aborted(started_at, duration_ms, message, &done, e)
+        }
+    };
+    if let Err(e) = record.append(name) {
+        tracing::warn!("Failed to record the deploy in the history: {}", e);
+    }
+    let outcome = result?;
+    if !outcome.failures.is_empty() {
+        tracing::warn!(
+            "{} action(s) failed, to be listed at the end",
+            outcome.failures.len()
+        );
+        failures::record(name, &outcome.failures);
+    }
+    let succeeded = actions[..outcome.done]
+        .iter()
+        .filter(|action| !outcome.failures.iter().any(|(failed, _)| failed == *action));
+    let saved = images::saved(succeeded);
+    if saved > 0 {
+        tracing::info!("Optimizing images saved {}", ByteSize::b(saved));
+    }
+    if outcome.done < actions.len() {
+        bail!(
+            "Deployment cancelled, {} action(s) left undone; use `deploy --resume` to continue",
+            actions.len() - outcome.done
+        );
+    }
+    if outcome.failures.is_empty() {
+        journal.remove()?;
+    }
+    if args.verify {
+        let uploaded: Vec<_> = actions[..outcome.done]
+            .iter()
+            .filter(|action| !outcome.failures.iter().any(|(failed, _)| failed == *action))
+            .filter_map(|action| match action {
+                Action::Upload(entry) => Some(entry),
+                Action::DeleteRemote(_) => None,
             })
-            .fold(Vec::new(), |mut acc, action| {
-                // After the deletion of a directory, skip the deletion of its children; otherwise,
-                // we would get errors because the children would already be gone when we try to
-                // delete them.
-                match (acc.last(), &action) {
-                    (Some(DeleteRemote(last)), DeleteRemote(cur))
-                        if cur.path.starts_with(&format!("{}/", last.path)) => {}
-                    _ => acc.push(action),
-                };
-                acc
+            .collect();
+        verify_uploads(name, client, &options, control, &uploaded)?;
+    }
+    let deleted = actions.iter().any(|a| matches!(a, Action::DeleteRemote(_)));
+    if deleted && outcome.failures.is_empty() && site.clean_empty_dirs.unwrap_or_default() {
+        clean_empty_dirs(site, client)?;
+    }
+    snapshot::refresh(name, client)?;
+    Ok(outcome.summary(&actions))
+}
+
+/// Check that the files just uploaded have the same SHA-1 hashes on the site as locally, uploading
+/// again those that differ, up to [`POLICY_RETRIES`] times, with the same batches and memory
+/// budget as the deploy. The files that still differ are recorded as failures.
+fn verify_uploads(
+    name: &str,
+    client: &Client,
+    options: &ExecuteOptions,
+    control: &Control,
+    uploaded: &[&Entry],
+) -> Result<()> {
+    if uploaded.is_empty() {
+        return Ok(());
+    }
+    tracing::info!("Verifying {} uploaded file(s)", uploaded.len());
+    let mut pending = uploaded
+        .iter()
+        .map(|entry| {
+            let sha1_sum = match &entry.info {
+                Some(info) if !info.sha1_sum.is_empty() => info.sha1_sum.clone(),
+                _ => trees::sha1_bytes(&entry.read()?),
+            };
+            Ok((*entry, sha1_sum))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for attempt in 0..=POLICY_RETRIES {
+        let remote = trees::remote_tree(&client.list()?);
+        pending.retain(|(entry, sha1_sum)| {
+            !remote.iter().any(|r| {
+                r.path == entry.path && r.info.as_ref().is_some_and(|i| i.sha1_sum == *sha1_sum)
             })
+        });
+        if pending.is_empty() {
+            tracing::info!("All uploaded files match");
+            return Ok(());
+        }
+        for (entry, _) in &pending {
+            tracing::warn!("Uploaded file differs from the local file: {}", entry.path);
+        }
+        if attempt == POLICY_RETRIES {
+            break;
+        }
+        tracing::info!("Uploading {} file(s) again", pending.len());
+        let actions: Vec<_> = pending
+            .iter()
+            .map(|(entry, _)| Action::Upload((*entry).clone()))
+            .collect();
+        let outcome = Executor::new(client, options.clone())
+            .control(control.clone())
+            .execute(&actions)?;
+        if outcome.done < actions.len() {
+            break;
+        }
+    }
+    let mismatches: Vec<_> = pending
+        .into_iter()
+        .map(|(entry, _)| {
+            let error = "the file on the site differs from the local file".to_owned();
+            (Action::Upload(entry.clone()), error)
+        })
+        .collect();
+    failures::record(name, &mismatches);
+    Ok(())
+}
+
+/// Delete the remote directories that a deploy left with no files in them, which the API does not
+/// remove by itself.
+fn clean_empty_dirs(site: &Site, client: &Client) -> Result<()> {
+    let prefix = site.remote_prefix();
+    let mut remote = trees::remote_tree(&client.list()?);
+    trees::scope_remote(&mut remote, &prefix);
+    let empty = trees::empty_remote_dirs(&remote, &prefix, site.protection()?);
+    if empty.is_empty() {
+        return Ok(());
+    }
+    for path in &empty {
+        tracing::info!("Deleting empty directory: {}", path);
+    }
+    let paths: Vec<_> = empty.iter().map(String::as_str).collect();
+    client.delete(&paths)?;
+    Ok(())
+}
+
+/// Reports the progress of a deploy: completed actions are taken off the journal, with `--json`,
+/// events are printed to the standard output as JSON lines, and with `--tui`, they are shown in
+/// the terminal UI.
+struct DeployProgress<'a> {
+    /// Name of the site being deployed.
+    site: String,
+    /// Whether events are printed at all.
+    json: bool,
+    /// Where to record the actions that are done.
+    journal: &'a Journal,
+    /// Terminal UI showing the progress, if any.
+    monitor: Option<&'a tui::Monitor>,
+}
+
+impl Progress for DeployProgress<'_> {
+    fn event(&self, action: &Action, event: &Event) {
+        if let Some(monitor) = self.monitor {
+            monitor.event(action, event);
+        }
+        if !self.json {
+            return;
+        }
+        let mut record = json!({
+            "event": event.name(),
+            "site": self.site,
+            "action": action.kind(),
+            "path": action.entry().path,
+            "bytes": action.bytes(),
+        });
+        match event {
+            Event::Completed { duration } => {
+                record["duration_ms"] = json!(duration.as_millis() as u64);
+            }
+            Event::Failed { duration, error } => {
+                record["duration_ms"] = json!(duration.as_millis() as u64);
+                record["error"] = json!(error);
+            }
+            _ => {}
+        }
+        println!("{}", record);
+    }
+
+    fn completed(&self, batch: &[Action]) {
+        if let Err(e) = self.journal.complete(batch) {
+            tracing::warn!("Failed to update the deploy journal: {}", e);
+        }
     }
 }
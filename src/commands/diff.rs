@@ -0,0 +1,107 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+use crate::params::{DiffArgs, Params};
+use crate::trees;
+use anyhow::{bail, Result};
+use similar::TextDiff;
+
+/// Show the differences between the remote and local versions of a file.
+pub fn diff(params: &Params, args: &DiffArgs) -> Result<()> {
+    let path = args.path.trim_matches('/');
+    for (name, site) in params.sites()? {
+        tracing::info!("Comparing {} on site {}", path, name);
+        let local = trees::merged_tree(&site.source_dirs(), false, &site.tree_options())?;
+        let local = match local.iter().find(|e| e.path == path && e.is_file()) {
+            Some(entry) => Some(entry.read()?.into_owned()),
+            None => None,
+        };
+        let remote = site.fetcher()?(path)?;
+        let (old_name, new_name) = (format!("remote/{}", path), format!("local/{}", path));
+        let diff = match (&remote, &local) {
+            (None, None) => bail!("{} exists neither locally nor on site {}", path, name),
+            (Some(remote), Some(local)) if remote == local => None,
+            (remote, local) => unified_diff(
+                remote.as_deref().map(|r| (r, old_name.as_str())),
+                local.as_deref().map(|l| (l, new_name.as_str())),
+                args.context,
+            ),
+        };
+        match diff {
+            Some(diff) => print!("{}", diff),
+            None => tracing::info!("No differences in {}", path),
+        }
+    }
+    Ok(())
+}
+
+/// A version of a file: its contents and the name to show for it, or `None` if it does not exist.
+type Version<'a> = Option<(&'a [u8], &'a str)>;
+
+/// Name to show for a version of a file.
+fn name<'a>(version: Version<'a>) -> &'a str {
+    version.map_or("/dev/null", |(_, name)| name)
+}
+
+/// Text of a version of a file (empty if it does not exist), or `None` if it is binary.
+fn text<'a>(version: Version<'a>) -> Option<&'a str> {
+    match version {
+        Some((content, _)) => std::str::from_utf8(content)
+            .ok()
+            .filter(|text| !text.contains('\0')),
+        None => Some(""),
+    }
+}
+
+/// Make a unified diff of two versions of a file. Returns `None` if they are the same.
+fn unified_diff(old: Version, new: Version, context: usize) -> Option<String> {
+    let (Some(old_text), Some(new_text)) = (text(old), text(new)) else {
+        return Some(format!(
+            "Binary files {} and {} differ\n",
+            name(old),
+            name(new)
+        ));
+    };
+    let diff = TextDiff::from_lines(old_text, new_text)
+        .unified_diff()
+        .context_radius(context)
+        .header(name(old), name(new))
+        .to_string();
+    (!diff.is_empty()).then_some(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let old = b"a\nb\nc\n";
+        let new = b"a\nB\nc\n";
+        let diff = unified_diff(Some((old, "remote/x")), Some((new, "local/x")), 3).unwrap();
+        assert_eq!(
+            diff,
+            "--- remote/x\n+++ local/x\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n"
+        );
+        let diff = unified_diff(None, Some((b"a\n", "local/x")), 3).unwrap();
+        assert!(diff.starts_with("--- /dev/null\n+++ local/x\n"));
+        let diff = unified_diff(Some((b"\x89PNG\0", "remote/x.png")), None, 3).unwrap();
+        assert_eq!(diff, "Binary files remote/x.png and /dev/null differ\n");
+        assert_eq!(unified_diff(Some((old, "a")), Some((old, "b")), 3), None);
+    }
+}
@@ -0,0 +1,205 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+use super::account;
+use crate::params::{redact_url, Config, Params, Site};
+use crate::tls;
+use anyhow::{bail, Result};
+use neocities_client::{Auth, Client, Error, ErrorKind};
+use std::path::Path;
+
+/// Diagnose problems with the configuration and the site(s).
+pub fn doctor(params: &Params) -> Result<()> {
+    let mut report = Report::default();
+
+    let config_file = params.config_file();
+    println!("Checking configuration file {:?}", config_file);
+    if !config_file.exists() {
+        report.fail(
+            "Configuration file does not exist",
+            "Run `neocities-deploy config` to configure a site, or pass `--config`",
+        );
+    } else {
+        match Config::load(&config_file) {
+            Ok(config) if config.sites.is_empty() => report.fail(
+                "No sites are configured",
+                "Run `neocities-deploy config` to configure a site",
+            ),
+            Ok(_) => report.ok("Configuration file is valid"),
+            Err(e) => report.fail(
+                &format!("Configuration file is invalid: {}", e),
+                "Fix the syntax error above, or run `neocities-deploy config` to recreate it",
+            ),
+        }
+    }
+
+    if report.failures == 0 {
+        for (name, site) in params.sites()? {
+            println!("Checking site {}", name);
+            check_site(params, &mut report, &name, &site);
+        }
+    }
+
+    if report.failures > 0 {
+        bail!("{} problem(s) found", report.failures);
+    }
+    println!("No problems found");
+    Ok(())
+}
+
+/// Find out whether the account of a site is free, if the site doesn’t say, and save the answer.
+fn check_account(params: &Params, report: &mut Report, name: &str, site: &Site, client: &Client) {
+    if site.free_account.is_some() {
+        return;
+    }
+    let free_account = match account::detect(client) {
+        Ok(free_account) => free_account,
+        Err(e) => {
+            return report.warn(
+                &format!("Account type is not set, and could not be detected: {}", e),
+                "Set `free_account` so that disallowed file types are skipped on free accounts",
+            )
+        }
+    };
+    let kind = if free_account { "free" } else { "paid" };
+    match account::save(params, name, free_account) {
+        Ok(()) => report.ok(&format!("Account is {} (detected, and saved)", kind)),
+        Err(e) => report.warn(
+            &format!(
+                "Account is {} (detected), but it could not be saved: {:#}",
+                kind, e
+            ),
+            &format!("Set `free_account = {}` for the site", free_account),
+        ),
+    }
+}
+
+/// Check the configuration of a single site.
+fn check_site(params: &Params, report: &mut Report, name: &str, site: &Site) {
+    if Path::new(&site.path).is_dir() {
+        report.ok(&format!("Local path {:?} exists", site.path));
+    } else {
+        report.fail(
+            &format!(
+                "Local path {:?} does not exist or is not a directory",
+                site.path
+            ),
+            "Create the directory, or fix the `path` key in the configuration file",
+        );
+    }
+
+    if let Some(proxy) = &site.proxy {
+        let proxy = redact_url(proxy);
+        match site.proxy() {
+            Ok(_) => report.ok(&format!("Proxy {:?} is valid", proxy)),
+            Err(e) => {
+                report.fail(
+                    &format!("Proxy {:?} is invalid: {}", proxy, e),
+                    "Fix the `proxy`, `proxy_user` and `proxy_pass` keys in the configuration file",
+                );
+                return;
+            }
+        }
+    }
+
+    if let Some(ca_cert) = &site.ca_cert {
+        match tls::config(Some(Path::new(ca_cert)), false) {
+            Ok(_) => report.ok(&format!("CA certificate {:?} is valid", ca_cert)),
+            Err(e) => {
+                report.fail(
+                    &format!("{:#}", e),
+                    "Fix the `ca_cert` key in the configuration file",
+                );
+                return;
+            }
+        }
+    }
+    if site.insecure_skip_verify == Some(true) {
+        report.warn(
+            "TLS certificates are not verified",
+            "Remove `insecure_skip_verify` once you are done testing, or use `ca_cert` instead",
+        );
+    }
+
+    let client = match site.build_client() {
+        Ok(client) => client,
+        Err(e) => return report.fail(&format!("Could not build client: {}", e), ""),
+    };
+    match client.info() {
+        Ok(info) => {
+            report.ok(&format!("Credentials are valid for site {}", info.sitename));
+            check_account(params, report, name, site, &client);
+        }
+        Err(Error::Api {
+            kind: ErrorKind::InvalidAuth,
+            ..
+        }) => report.fail(
+            "Authentication failed",
+            "Check the credentials, or run `neocities-deploy config` to log in again",
+        ),
+        Err(Error::Transport(e)) if site.proxy.is_some() => report.fail(
+            &format!("Could not reach the API through the proxy: {}", e),
+            "Check that the proxy is running and accepts connections",
+        ),
+        Err(Error::Transport(e)) => report.fail(
+            &format!("Could not reach the API: {}", e),
+            "Check your network connection",
+        ),
+        Err(e) => report.fail(&format!("API request failed: {}", e), ""),
+    }
+
+    if matches!(site.auth, Some(Auth::Credentials(_, _))) {
+        report.warn(
+            "Password is stored in the configuration file",
+            "Run `neocities-deploy key` to replace it with an API key",
+        );
+    }
+}
+
+/// Tally of the checks, which are printed as they are made.
+#[derive(Default)]
+struct Report {
+    failures: usize,
+}
+
+impl Report {
+    /// Report a successful check.
+    fn ok(&mut self, message: &str) {
+        println!("  ✔ {}", message);
+    }
+
+    /// Report a potential problem, which does not count as a failure.
+    fn warn(&mut self, message: &str, hint: &str) {
+        println!("  ! {}", message);
+        Self::hint(hint);
+    }
+
+    /// Report a failed check.
+    fn fail(&mut self, message: &str, hint: &str) {
+        self.failures += 1;
+        println!("  ✘ {}", message);
+        Self::hint(hint);
+    }
+
+    /// Print a hint on how to fix a problem.
+    fn hint(hint: &str) {
+        if !hint.is_empty() {
+            println!("    → {}", hint);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Renaming of assets to names holding a hash of their contents, so that browsers and caches
+//! never serve stale versions of them.
+
+use super::check_links::{is_html, links, resolve, Link};
+use crate::params::Site;
+use crate::trees::{self, Entry, PathFilter};
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// Number of hexadecimal digits of the hash added to the names of assets.
+const HASH_LEN: usize = 8;
+
+/// Rename the assets matched by the site’s `fingerprint` globs to names holding a hash of their
+/// contents (`app.css` becomes `app.3f9ab2c1.css`), and rewrite the references to them in the
+/// HTML files and style sheets of the tree, which is sorted again afterwards.
+///
+/// Style sheets are renamed after their own references are rewritten, so that their names change
+/// whenever an asset they use does. References from one style sheet to another are left alone.
+/// The previous versions of the assets are no longer in the local tree, so deploys delete them
+/// from the site.
+pub fn apply(local: &mut [Entry], site: &Site) -> Result<()> {
+    let globs = match &site.fingerprint {
+        Some(globs) if !globs.is_empty() => globs,
+        _ => return Ok(()),
+    };
+    let filter = PathFilter::new(globs, &[])?;
+    let is_asset =
+        |entry: &Entry| entry.is_file() && !is_html(&entry.path) && filter.is_selected(&entry.path);
+    let mut hashes = HashMap::new();
+    // Other assets first, since style sheets may refer to them.
+    for style_sheets in [false, true] {
+        for entry in local
+            .iter_mut()
+            .filter(|entry| is_asset(entry) && is_css(&entry.path) == style_sheets)
+        {
+            if style_sheets {
+                rewrite(entry, &hashes)?;
+            }
+            let hash = trees::sha1_bytes(&entry.read()?)[..HASH_LEN].to_owned();
+            let path = fingerprinted(&entry.path, &hash);
+            tracing::debug!("Fingerprinting {} as {}", entry.path, path);
+            hashes.insert(std::mem::replace(&mut entry.path, path), hash);
+        }
+    }
+    for entry in local
+        .iter_mut()
+        .filter(|entry| entry.is_file() && !is_asset(entry))
+        .filter(|entry| is_html(&entry.path) || is_css(&entry.path))
+    {
+        rewrite(entry, &hashes)?;
+    }
+    local.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(())
+}
+
+/// Whether a path is that of a style sheet.
+fn is_css(path: &str) -> bool {
+    path.to_lowercase().ends_with(".css")
+}
+
+/// Insert a hash in the file name of a path or link, before its extension (or at its end, if it
+/// has none). The query and fragment of a link are kept.
+fn fingerprinted(path: &str, hash: &str) -> String {
+    let end = path.find(['?', '#']).unwrap_or(path.len());
+    let name = path[..end].rfind('/').map_or(0, |slash| slash + 1);
+    let at = match path[name..end].rfind('.') {
+        Some(dot) if dot > 0 => name + dot,
+        _ => end,
+    };
+    format!("{}.{}{}", &path[..at], hash, &path[at..])
+}
+
+/// Rewrite the references of an HTML file or style sheet to fingerprinted assets, given the
+/// hashes of the assets by their original paths.
+fn rewrite(entry: &mut Entry, hashes: &HashMap<String, String>) -> Result<()> {
+    let content = entry.read()?;
+    let Ok(text) = std::str::from_utf8(&content) else {
+        tracing::warn!(
+            "Not fingerprinting the references of {}, it isn’t UTF-8",
+            entry.path
+        );
+        return Ok(());
+    };
+    let Some(rewritten) = rewritten(&entry.path, text, hashes) else {
+        return Ok(());
+    };
+    if let Some(info) = &mut entry.info {
+        info.size = rewritten.len() as u64;
+        info.sha1_sum = trees::sha1_bytes(rewritten.as_bytes());
+    }
+    entry.content = Some(rewritten.into_bytes().into());
+    Ok(())
+}
+
+/// The contents of a file with its references to fingerprinted assets rewritten, or `None` if it
+/// has no such references.
+fn rewritten(path: &str, text: &str, hashes: &HashMap<String, String>) -> Option<String> {
+    let found = match is_html(path) {
+        true => links(text),
+        false => css_links(text),
+    };
+    let mut rewritten = String::with_capacity(text.len());
+    let mut copied = 0;
+    for link in found {
+        let Some(Some(target)) = resolve(path, &link.value()) else {
+            continue;
+        };
+        let Some(hash) = hashes.get(&target) else {
+            continue;
+        };
+        rewritten.push_str(&text[copied..link.offset]);
+        rewritten.push_str(&fingerprinted(link.raw, hash));
+        copied = link.offset + link.raw.len();
+    }
+    if copied == 0 {
+        return None;
+    }
+    rewritten.push_str(&text[copied..]);
+    Some(rewritten)
+}
+
+/// Extract the references of a style sheet: the URLs of its `url()` functions and `@import`
+/// rules. Comments are skipped.
+fn css_links(css: &str) -> Vec<Link<'_>> {
+    let bytes = css.as_bytes();
+    let at = |i: usize, s: &str| {
+        bytes.len() >= i + s.len() && bytes[i..i + s.len()].eq_ignore_ascii_case(s.as_bytes())
+    };
+    let mut links = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if at(i, "/*") {
+            i = css[i + 2..]
+                .find("*/")
+                .map_or(bytes.len(), |end| i + end + 4);
+            continue;
+        }
+        let is_url = at(i, "url(");
+        if !is_url && !at(i, "@import") {
+            i += 1;
+            continue;
+        }
+        let start = i + if is_url { 4 } else { 7 };
+        let start = start + css[start..].len() - css[start..].trim_start().len();
+        let (start, end) = match bytes.get(start) {
+            Some(&quote @ (b'"' | b'\'')) => {
+                let start = start + 1;
+                let end = css[start..]
+                    .find(quote as char)
+                    .map_or(bytes.len(), |end| start + end);
+                (start, end)
+            }
+            Some(_) if is_url => (
+                start,
+                css[start..]
+                    .find(')')
+                    .map_or(bytes.len(), |end| start + end),
+            ),
+            // An `@import url(...)`, found on the next iteration.
+            _ => {
+                i = start;
+                continue;
+            }
+        };
+        let raw = css[start..end].trim_end();
+        links.push(Link { offset: start, raw });
+        i = end;
+    }
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trees::{MemoryTree, TreeOptions, TreeSource};
+    use neocities_client::Auth;
+
+    #[test]
+    fn test_fingerprinted() {
+        assert_eq!(fingerprinted("app.css", "3f9ab2c1"), "app.3f9ab2c1.css");
+        assert_eq!(fingerprinted("js/app.min.js", "ab"), "js/app.min.ab.js");
+        assert_eq!(fingerprinted("../a.png?v=1#x", "ab"), "../a.ab.png?v=1#x");
+        assert_eq!(fingerprinted("v1.0/LICENSE", "ab"), "v1.0/LICENSE.ab");
+        assert_eq!(fingerprinted(".htaccess", "ab"), ".htaccess.ab");
+    }
+
+    #[test]
+    fn test_css_links() {
+        let css = r#"@import "base.css"; /* url(commented.png) */
+            body { background: URL( 'img/bg.png' ) }
+            @font-face { src: url(fonts/a.woff2) format("woff2"); }
+            @import url("print.css") print;"#;
+        let found: Vec<_> = css_links(css).iter().map(|link| link.raw).collect();
+        assert_eq!(
+            found,
+            ["base.css", "img/bg.png", "fonts/a.woff2", "print.css"]
+        );
+        for link in css_links(css) {
+            assert_eq!(&css[link.offset..link.offset + link.raw.len()], link.raw);
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut tree = MemoryTree::default();
+        tree.add_file(
+            "index.html",
+            &br#"<link href="css/app.css"><img src="/img/cat.png"><a href="about.html">"#[..],
+        );
+        tree.add_file("about.html", &b"<img src=img/cat.png>"[..]);
+        tree.add_dir("css");
+        tree.add_file(
+            "css/app.css",
+            &b"body { background: url(../img/cat.png) }"[..],
+        );
+        tree.add_dir("img");
+        tree.add_file("img/cat.png", &b"meow"[..]);
+        let mut local = tree.tree(true, &TreeOptions::default()).unwrap();
+        let mut site = Site::new(Auth::from("key"), "");
+        site.fingerprint = Some(vec!["*.css".to_owned(), "img/**".to_owned()]);
+        apply(&mut local, &site).unwrap();
+
+        let cat = format!("cat.{}.png", &trees::sha1_bytes(b"meow")[..HASH_LEN]);
+        let css = format!("body {{ background: url(../img/{}) }}", cat);
+        let app = format!("app.{}.css", &trees::sha1_bytes(css.as_bytes())[..HASH_LEN]);
+        let paths: Vec<_> = local.iter().map(|entry| entry.path.as_str()).collect();
+        let expected = [
+            "about.html".to_owned(),
+            "css".to_owned(),
+            format!("css/{}", app),
+            "img".to_owned(),
+            format!("img/{}", cat),
+            "index.html".to_owned(),
+        ];
+        assert_eq!(paths, expected);
+        let read = |path: &str| {
+            let entry = local.iter().find(|entry| entry.path == path).unwrap();
+            assert_eq!(
+                entry.info.as_ref().unwrap().size,
+                entry.read().unwrap().len() as u64
+            );
+            String::from_utf8(entry.read().unwrap().into_owned()).unwrap()
+        };
+        assert_eq!(read(&format!("css/{}", app)), css);
+        assert_eq!(
+            read("index.html"),
+            format!(
+                r#"<link href="css/{}"><img src="/img/{}"><a href="about.html">"#,
+                app, cat
+            )
+        );
+        assert_eq!(read("about.html"), format!("<img src=img/{}>", cat));
+    }
+}
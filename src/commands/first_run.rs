@@ -0,0 +1,66 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+use super::{config, init};
+use crate::ci;
+use crate::params::{InitArgs, Params, PROJECT_CONFIG_FILE};
+use anyhow::{bail, Result};
+
+/// Make sure there is a configuration file before running a command that needs one.
+///
+/// On the first run, there is none: when running interactively, the user is offered to configure
+/// a site right away (in the user’s config file, or in a project config file in the current
+/// directory, unless `--config` or `--profile` says where it goes), and the command then goes on.
+/// Otherwise, the command fails, explaining how to configure a site.
+pub fn ensure_config(params: &Params) -> Result<()> {
+    let path = params.config_file();
+    if path.exists() {
+        return Ok(());
+    }
+    let chosen = params.config.is_some() || params.profile.is_some();
+    if !ci::interactive() {
+        let mut message = format!("No configuration file found at {:?}.", path);
+        message.push_str(" Run `neocities-deploy config` to configure a site");
+        if !chosen {
+            message.push_str(
+                ", or `neocities-deploy init` to configure the site in the current directory",
+            );
+        }
+        bail!(message);
+    }
+
+    eprintln!("Welcome to neocities-deploy! There is no configuration file yet.");
+    let user_config = format!("In {}", path.display());
+    let project_config = format!("In {} in the current directory", PROJECT_CONFIG_FILE);
+    let options = match chosen {
+        true => vec![user_config.as_str()],
+        false => vec![user_config.as_str(), project_config.as_str()],
+    };
+    let answer = inquire::Select::new("Where should the site be configured?", options)
+        .with_help_message("Press Esc to quit without configuring a site")
+        .prompt_skippable()?;
+    match answer {
+        Some(answer) if answer == user_config => config::interactive(params)?,
+        Some(_) => init(params, &InitArgs::default())?,
+        None => bail!("No configuration file found at {:?}", path),
+    }
+    if !params.config_file().exists() {
+        bail!("No site was configured");
+    }
+    Ok(())
+}
@@ -0,0 +1,264 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! A per-site log of past deploys, kept in the data directory.
+
+use crate::executor::Outcome;
+use crate::params::{Config, HistoryArgs, Params};
+use crate::plan::Action;
+use anyhow::Result;
+use bytesize::ByteSize;
+use parse_display::Display;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Show the past deploys of the site(s).
+pub fn history(params: &Params, args: &HistoryArgs) -> Result<()> {
+    for (name, _) in params.sites()? {
+        println!("Deploy history of site {}", name);
+        let records = Record::load(&name)?;
+        let skip = records.len().saturating_sub(args.limit);
+        for record in &records[skip..] {
+            println!("{}", record);
+            if args.actions {
+                for action in &record.actions {
+                    println!("    {}", action);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// How a deploy ended.
+#[derive(Clone, Copy, Debug, PartialEq, Display, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[display(style = "snake_case")]
+pub enum Status {
+    /// Every action was done.
+    Success,
+    /// Some actions failed.
+    Failure,
+    /// The deploy was stopped before all actions were done.
+    Cancelled,
+}
+
+/// An action taken by a deploy.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TakenAction {
+    /// Kind of action (`upload` or `delete`).
+    pub action: String,
+    /// Path of the remote entry.
+    pub path: String,
+    /// Whether the action failed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub failed: bool,
+}
+
+/// A past deploy of a site.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// When the deploy started, in RFC 3339 format.
+    pub started_at: String,
+    /// How long the deploy took, in milliseconds.
+    pub duration_ms: u64,
+    /// How the deploy ended.
+    pub status: Status,
+    /// Message given with `--message`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Number of bytes uploaded.
+    pub bytes: u64,
+    /// Actions that were taken, successfully or not.
+    pub actions: Vec<TakenAction>,
+    /// Error that stopped the deploy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl fmt::Display for TakenAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.action, self.path)?;
+        if self.failed {
+            write!(f, " (failed)")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = |kind: &str| {
+            self.actions
+                .iter()
+                .filter(|a| a.action == kind && !a.failed)
+                .count()
+        };
+        let failed = self.actions.iter().filter(|a| a.failed).count();
+        write!(
+            f,
+            "{}  {:<9}  {} uploaded ({}), {} deleted, {} failed",
+            self.started_at,
+            self.status.to_string(),
+            count("upload"),
+            ByteSize(self.bytes),
+            count("delete"),
+            failed,
+        )?;
+        if let Some(message) = &self.message {
+            write!(f, "  {}", message)?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, "  ({})", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Record {
+    /// Make the record of a deploy from its actions and outcome.
+    pub fn new(
+        started_at: String,
+        duration_ms: u64,
+        message: Option<String>,
+        actions: &[Action],
+        outcome: &Outcome,
+    ) -> Self {
+        let failed = |action: &Action| outcome.failures.iter().any(|(f, _)| f == action);
+        let status = match (outcome.done < actions.len(), outcome.failures.is_empty()) {
+            (true, _) => Status::Cancelled,
+            (false, true) => Status::Success,
+            (false, false) => Status::Failure,
+        };
+        let taken = &actions[..outcome.done];
+        Self {
+            started_at,
+            duration_ms,
+            status,
+            message,
+            bytes: taken.iter().filter(|a| !failed(a)).map(Action::bytes).sum(),
+            actions: taken
+                .iter()
+                .map(|action| TakenAction {
+                    action: action.kind().to_owned(),
+                    path: action.entry().path.clone(),
+                    failed: failed(action),
+                })
+                .collect(),
+            error: None,
+        }
+    }
+
+    /// Make the record of a deploy that was stopped by an error, given the actions that were
+    /// done before it.
+    pub fn aborted(
+        started_at: String,
+        duration_ms: u64,
+        message: Option<String>,
+        done: &[Action],
+        error: &anyhow::Error,
+    ) -> Self {
+        let outcome = Outcome {
+            done: done.len(),
+            failures: vec![],
+        };
+        Self {
+            status: Status::Failure,
+            error: Some(error.to_string()),
+            ..Self::new(started_at, duration_ms, message, done, &outcome)
+        }
+    }
+
+    /// Append the record to the history of a site.
+    pub fn append(&self, site: &str) -> Result<()> {
+        let path = Self::file(site);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Load the history of a site, oldest deploy first.
+    pub fn load(site: &str) -> Result<Vec<Self>> {
+        let path = Self::file(site);
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        fs::read_to_string(path)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+
+    /// Path of the history file for a site.
+    pub fn file(site: &str) -> PathBuf {
+        Config::site_data_file("history", site, "jsonl")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trees::{Entry, FileInfo};
+
+    fn upload(path: &str, size: u64) -> Action {
+        Action::Upload(Entry {
+            path: path.to_owned(),
+            info: Some(FileInfo {
+                size,
+                sha1_sum: String::new(),
+                modified: None,
+            }),
+            local_path: None,
+            content: None,
+        })
+    }
+
+    #[test]
+    fn test_record() {
+        let actions = [
+            upload("a.css", 100),
+            upload("b.html", 20),
+            upload("c.js", 3),
+        ];
+        let outcome = Outcome {
+            done: 2,
+            failures: vec![(actions[1].clone(), "500".to_owned())],
+        };
+        let record = Record::new(
+            "2024-05-01T10:00:00Z".to_owned(),
+            0,
+            None,
+            &actions,
+            &outcome,
+        );
+        assert_eq!(record.status, Status::Cancelled);
+        assert_eq!(record.bytes, 100);
+        assert_eq!(
+            record.to_string(),
+            "2024-05-01T10:00:00Z  cancelled  1 uploaded (100 B), 0 deleted, 1 failed"
+        );
+        assert_eq!(record.actions[1].to_string(), "upload b.html (failed)");
+    }
+}
@@ -0,0 +1,87 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+use crate::params::{InfoArgs, Params};
+use anyhow::Result;
+use neocities_client::response::Info;
+use serde_json::{json, Map, Value};
+
+/// Show information about the site(s).
+pub fn info(params: &Params, args: &InfoArgs) -> Result<()> {
+    let mut infos = Map::new();
+    for (name, site) in params.sites()? {
+        let client = site.build_client()?;
+        let info = match client.info() {
+            Ok(info) => info,
+            Err(e) if params.ignore_errors => {
+                tracing::error!("{}", e);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if args.json {
+            infos.insert(name, to_json(&info));
+        } else {
+            print_info(&name, &info);
+        }
+    }
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&infos)?);
+    }
+    Ok(())
+}
+
+/// Print the site information in a human-readable format.
+fn print_info(name: &str, info: &Info) {
+    let none = "—".to_owned();
+    println!("Info for site {}", name);
+    println!("{:>18}  {}", "Sitename:", info.sitename);
+    println!("{:>18}  {}", "Views:", info.views);
+    println!("{:>18}  {}", "Hits:", info.hits);
+    println!("{:>18}  {}", "Tags:", info.tags.join(", "));
+    println!(
+        "{:>18}  {}",
+        "Domain:",
+        info.domain.as_ref().unwrap_or(&none)
+    );
+    println!("{:>18}  {}", "Created at:", info.created_at);
+    println!(
+        "{:>18}  {}",
+        "Last updated:",
+        info.last_updated.as_ref().unwrap_or(&none)
+    );
+    println!(
+        "{:>18}  {}",
+        "Latest IPFS hash:",
+        info.latest_ipfs_hash.as_ref().unwrap_or(&none)
+    );
+}
+
+/// Convert the site information to a JSON value.
+fn to_json(info: &Info) -> Value {
+    json!({
+        "sitename": info.sitename,
+        "views": info.views,
+        "hits": info.hits,
+        "tags": info.tags,
+        "domain": info.domain,
+        "created_at": info.created_at,
+        "last_updated": info.last_updated,
+        "latest_ipfs_hash": info.latest_ipfs_hash,
+    })
+}
@@ -0,0 +1,57 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+use super::config::{login, login_with};
+use crate::params::{Config, InitArgs, Params, PROJECT_CONFIG_FILE};
+use anyhow::{bail, Result};
+use std::path::Path;
+
+/// Create a configuration file for the site in the current directory.
+pub fn init(params: &Params, args: &InitArgs) -> Result<()> {
+    let config_file = Path::new(PROJECT_CONFIG_FILE);
+    if config_file.exists() && !args.force {
+        bail!(
+            "{} already exists; use --force to overwrite it",
+            PROJECT_CONFIG_FILE
+        );
+    }
+
+    let interactive = args.login.api_key.is_none() && args.login.username.is_none();
+    let (sitename, mut site) = match login_with(params, &args.login)? {
+        Some(login) => login,
+        None => login(params)?,
+    };
+
+    site.path = ".".to_owned();
+    site.free_account = match args.login.free_account {
+        None if interactive => Some(
+            inquire::Confirm::new("Free account?")
+                .with_default(true)
+                .with_help_message("Is this a free account?")
+                .prompt()?,
+        ),
+        free_account => free_account,
+    };
+
+    let name = args.login.name.clone().unwrap_or(sitename);
+    let mut config = Config::default();
+    config.insert_site(name.clone(), site);
+    config.save(config_file)?;
+    eprintln!("Site {} configured in {}", name, PROJECT_CONFIG_FILE);
+    Ok(())
+}
@@ -0,0 +1,164 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Persisting the actions of a deploy that are still to be done, so that an interrupted deploy
+//! can be resumed.
+
+use crate::params::Config;
+use crate::plan::Action;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Record of the pending actions of a deploy, kept in a file in the cache directory until the
+/// deploy finishes.
+///
+/// The file starts with a line holding all the actions of the deploy, followed by a line for each
+/// action done, appended as batches complete, so that the actions are only written once.
+pub struct Journal {
+    path: PathBuf,
+    actions: Vec<Action>,
+    done: Mutex<HashSet<Done>>,
+    file: Mutex<JournalWriter>,
+}
+
+/// The journal file open for appending, and whether a failed write may have left a partial line
+/// at its end.
+struct JournalWriter {
+    file: File,
+    torn: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JournalFile {
+    site: String,
+    pending: Vec<Action>,
+}
+
+/// An action done, by its kind and path.
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct Done {
+    action: String,
+    path: String,
+}
+
+impl Done {
+    fn new(action: &Action) -> Self {
+        Self {
+            action: action.kind().to_owned(),
+            path: action.entry().path.clone(),
+        }
+    }
+}
+
+impl Journal {
+    /// Start a journal for a deploy of a site, with all of its actions pending.
+    pub fn create(site: &str, actions: &[Action]) -> Result<Self> {
+        let path = Self::file(site);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = JournalFile {
+            site: site.to_owned(),
+            pending: actions.to_vec(),
+        };
+        // Write to a temporary file first, so that an interruption never leaves a truncated file.
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string(&file)? + "\n")?;
+        fs::rename(&tmp, &path)?;
+        Ok(Self {
+            file: Mutex::new(JournalWriter {
+                file: OpenOptions::new().append(true).open(&path)?,
+                torn: false,
+            }),
+            path,
+            actions: file.pending,
+            done: Mutex::default(),
+        })
+    }
+
+    /// Load the pending actions of an interrupted deploy of a site, if there is one.
+    pub fn load(site: &str) -> Result<Option<Vec<Action>>> {
+        let path = Self::file(site);
+        if !path.exists() {
+            return Ok(None);
+        }
+        tracing::debug!("Loading pending actions from {:?}", path);
+        let contents = fs::read_to_string(&path)?;
+        let mut lines = contents.lines();
+        let file: JournalFile = serde_json::from_str(lines.next().unwrap_or_default())?;
+        // A line cut short by an interruption is not a completed action.
+        let done: HashSet<Done> = lines
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        let mut pending = file.pending;
+        pending.retain(|action| !done.contains(&Done::new(action)));
+        Ok(Some(pending))
+    }
+
+    /// Mark actions as done.
+    pub fn complete(&self, actions: &[Action]) -> Result<()> {
+        let mut writer = self.file.lock().unwrap();
+        // End a partial line left by a failed write, so that it does not swallow these actions.
+        let mut lines = if writer.torn {
+            "\n".to_owned()
+        } else {
+            String::new()
+        };
+        for action in actions {
+            lines += &serde_json::to_string(&Done::new(action))?;
+            lines.push('\n');
+        }
+        writer.torn = true;
+        writer.file.write_all(lines.as_bytes())?;
+        writer.torn = false;
+        drop(writer);
+        let mut done = self.done.lock().unwrap();
+        done.extend(actions.iter().map(Done::new));
+        Ok(())
+    }
+
+    /// Actions still pending.
+    pub fn pending(&self) -> Vec<Action> {
+        let done = self.done.lock().unwrap();
+        self.actions
+            .iter()
+            .filter(|action| !done.contains(&Done::new(action)))
+            .cloned()
+            .collect()
+    }
+
+    /// Remove the journal, once there is nothing left to resume.
+    pub fn remove(self) -> Result<()> {
+        let Self { path, file, .. } = self;
+        drop(file);
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Path of the journal file for a site.
+    pub fn file(site: &str) -> PathBuf {
+        Config::site_cache_file("journal", site, "json")
+    }
+}
@@ -16,25 +16,82 @@
 ////////       You should have received a copy of the GNU General Public License           ////////
 ////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
 
-use crate::params::Params;
+use crate::ci;
+use crate::params::{Config, KeyArgs, Params, Site};
 use anyhow::Result;
 use neocities_client::Auth;
 
-/// Replace credentials with API keys in the config file.
-pub fn key(params: &Params) -> Result<()> {
+/// Replace credentials with API keys in the config file, or, with `--print`, print the keys.
+pub fn key(params: &Params, args: &KeyArgs) -> Result<()> {
+    if args.print {
+        return print_keys(params);
+    }
     let sites: Vec<_> = (params.sites()?)
         .into_iter()
-        .filter(|(_, site)| matches!(site.auth, Auth::Credentials(_, _)))
+        .filter(|(_, site)| matches!(site.auth, Some(Auth::Credentials(_, _))))
         .collect();
 
     if sites.is_empty() {
         eprintln!("No sites to get API keys for.");
         return Ok(());
     }
+    replace_credentials(params, sites)
+}
+
+/// Offer to replace the passwords of the selected sites with API keys, after a command used
+/// them. Nothing is asked when not running interactively, or if the config sets
+/// `offer_api_keys` to `false` in `[global]`.
+pub fn offer_api_keys(params: &Params) -> Result<()> {
+    if !ci::interactive() {
+        return Ok(());
+    }
+    let config = params.config()?;
+    if config.global.and_then(|global| global.offer_api_keys) == Some(false) {
+        return Ok(());
+    }
+    let file = Config::load_file(params.config_file())?;
+    let sites: Vec<_> = (params.sites()?)
+        .into_iter()
+        .filter(|(name, site)| {
+            site.auth_cmd.is_none()
+                && matches!(site.auth, Some(Auth::Credentials(_, _)))
+                && file.has_site(name)
+        })
+        .collect();
+    if sites.is_empty() {
+        return Ok(());
+    }
+    let names = sites
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    let question = format!(
+        "The config file holds the password of site(s) {}. Replace it with an API key?",
+        names.join(", ")
+    );
+    let replace = inquire::Confirm::new(&question)
+        .with_default(true)
+        .with_help_message("Set `offer_api_keys = false` in [global] to stop being asked")
+        .prompt()?;
+    match replace {
+        true => replace_credentials(params, sites),
+        false => Ok(()),
+    }
+}
 
-    let mut config = params.config()?;
+/// Get API keys for sites configured with credentials, and save them to the config file in place
+/// of the credentials.
+fn replace_credentials(params: &Params, sites: Vec<(String, Site)>) -> Result<()> {
+    let mut config = Config::load_file(params.config_file())?;
     for (name, site) in sites {
-        if matches!(site.auth, Auth::ApiKey(_)) {
+        if matches!(site.auth, Some(Auth::ApiKey(_))) {
+            continue;
+        }
+        if !config.has_site(&name) {
+            tracing::error!(
+                "Site {} is defined in an included file; replace its credentials there",
+                name
+            );
             continue;
         }
         println!("Getting API key for site {}", name);
@@ -45,13 +102,47 @@ pub fn key(params: &Params) -> Result<()> {
                 if !params.ignore_errors {
                     Err(e)
                 } else {
-                    log::error!("{}", e);
+                    tracing::error!("{}", e);
                     continue;
                 }
             }
         }?;
-        config.sites.get_mut(&name).unwrap().auth = Auth::ApiKey(key);
+        config.sites.get_mut(&name).unwrap().auth = Some(Auth::ApiKey(key));
     }
     config.save(params.config_file())?;
     Ok(())
 }
+
+/// Print the API key of each site, getting it from Neocities for the sites configured with
+/// credentials, without changing the config file. With several sites, each key is prefixed with
+/// the name of its site.
+fn print_keys(params: &Params) -> Result<()> {
+    let sites = params.sites()?;
+    if sites.is_empty() {
+        eprintln!("No sites to get API keys for.");
+        return Ok(());
+    }
+    tracing::warn!("Printing API keys; anyone who sees them can change your site(s)");
+    let several = sites.len() > 1;
+    for (name, site) in sites {
+        let key = match &site.auth {
+            Some(Auth::ApiKey(key)) => key.clone(),
+            _ => {
+                eprintln!("Getting API key for site {}", name);
+                match site.build_client()?.key() {
+                    Ok(key) => key,
+                    Err(e) if params.ignore_errors => {
+                        tracing::error!("{}", e);
+                        continue;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        };
+        match several {
+            true => println!("{}: {}", name, key),
+            false => println!("{}", key),
+        }
+    }
+    Ok(())
+}
@@ -16,32 +16,244 @@
 ////////       You should have received a copy of the GNU General Public License           ////////
 ////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
 
-use crate::{params::Params, trees};
+use super::{listing, preflight};
+use crate::params::{ListArgs, ListFormat, ListSort, Params, Site};
+use crate::style;
+use crate::trees::{self, Entry, PathFilter};
 use anyhow::Result;
 use bytesize::ByteSize;
+use chrono::DateTime;
+use itertools::{EitherOrBoth::*, Itertools};
+use neocities_client::response::ListEntry;
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
 
-/// List files on the site(s).
-pub fn list(params: &Params) -> Result<()> {
+/// List files on the site(s), or, with `--local`, `--local-only` or `--remote-only`, the local
+/// files or those on one side only.
+pub fn list(params: &Params, args: &ListArgs) -> Result<()> {
+    let mut sites = Map::new();
     for (name, site) in params.sites()? {
-        println!("Listing site {}", name);
-        let client = site.build_client()?;
-        let list = client.list().or_else(|e| {
-            if params.ignore_errors {
-                log::error!("{}", e);
-                Ok(vec![])
-            } else {
-                Err(e)
+        if args.format == ListFormat::Text {
+            match args.local {
+                true => println!("Listing local files of site {}", name),
+                false => println!("Listing site {}", name),
             }
-        })?;
-        let remote = trees::remote_tree(&list);
-        for entry in remote {
-            let (size, path) = if let Some(info) = entry.info {
-                (format!("{}", ByteSize(info.size)), entry.path)
-            } else {
-                ("".to_owned(), format!("{}/", entry.path))
-            };
-            println!("{:>10}  {}", size, path);
         }
+        let mut list = match args.local {
+            true => local_tree(&site, args)?.iter().map(to_list_entry).collect(),
+            false => remote_list(params, &name, &site, args)?,
+        };
+        sort(&mut list, args.sort, args.reverse);
+        let list = filter(list, args)?;
+        match args.format {
+            ListFormat::Text if args.tree => print_tree(&list, args.long),
+            ListFormat::Text => {
+                for entry in &list {
+                    let path = match entry.is_directory {
+                        true => style::directory(format!("{}/", entry.path)).to_string(),
+                        false => entry.path.clone(),
+                    };
+                    print_row(entry, &path, args.long);
+                }
+            }
+            ListFormat::Json => {
+                sites.insert(name, list.iter().map(to_json).collect());
+            }
+            ListFormat::Ndjson => {
+                for entry in &list {
+                    let mut record = to_json(entry);
+                    record["site"] = json!(name);
+                    println!("{}", record);
+                }
+            }
+        }
+    }
+    if args.format == ListFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&sites)?);
     }
     Ok(())
 }
+
+/// List the files on a site, or, with `--local-only` or `--remote-only`, the entries on one side
+/// only, matching the paths of the local and remote trees as a deploy would.
+fn remote_list(
+    params: &Params,
+    name: &str,
+    site: &Site,
+    args: &ListArgs,
+) -> Result<Vec<ListEntry>> {
+    let client = site.build_client()?;
+    let list = listing::list(params, name, site, &client).or_else(|e| {
+        if params.ignore_errors {
+            tracing::error!("{}", e);
+            Ok(vec![])
+        } else {
+            Err(e)
+        }
+    })?;
+    if !args.local_only && !args.remote_only {
+        return Ok(list);
+    }
+    let local = local_tree(site, args)?;
+    let mut remote = trees::remote_tree(&list);
+    trees::scope_remote(&mut remote, &site.remote_prefix());
+    let only = local
+        .into_iter()
+        .merge_join_by(remote, |a, b| a.path.cmp(&b.path))
+        .filter_map(|pair| match pair {
+            Left(local) if args.local_only => Some(local),
+            Right(remote) if args.remote_only => Some(remote),
+            _ => None,
+        });
+    Ok(match args.local_only {
+        true => only.map(|entry| to_list_entry(&entry)).collect(),
+        false => {
+            let paths: HashSet<String> = only.map(|entry| entry.path).collect();
+            list.into_iter()
+                .filter(|entry| paths.contains(&entry.path))
+                .collect()
+        }
+    })
+}
+
+/// Build the local tree of a site as a deploy would prepare it, with the paths of the
+/// fingerprinted assets and the sizes of the optimized images. Files are only hashed when their
+/// hashes are shown.
+fn local_tree(site: &Site, args: &ListArgs) -> Result<Vec<Entry>> {
+    let hash = args.long || args.format != ListFormat::Text;
+    let mut local = trees::merged_tree(&site.source_dirs(), hash, &site.tree_options())?;
+    preflight::prepare_local_tree(&mut local, site, false)?;
+    Ok(local)
+}
+
+/// Convert an entry of the local tree to a listing entry, as if it were on the site.
+fn to_list_entry(entry: &Entry) -> ListEntry {
+    let info = entry.info.as_ref();
+    ListEntry {
+        path: entry.path.clone(),
+        is_directory: info.is_none(),
+        updated_at: info
+            .and_then(|info| DateTime::from_timestamp(info.modified?, 0))
+            .map(|time| time.to_rfc2822())
+            .unwrap_or_default(),
+        size: info.map(|info| info.size),
+        sha1_hash: info
+            .map(|info| info.sha1_sum.clone())
+            .filter(|sha1| !sha1.is_empty()),
+    }
+}
+
+/// Sort a listing by a key, and then by path. Directories are sorted by the total size of the
+/// files in them.
+fn sort(list: &mut [ListEntry], by: ListSort, reverse: bool) {
+    let mut totals: HashMap<String, u64> = HashMap::new();
+    for entry in list.iter().filter(|entry| !entry.is_directory) {
+        for (slash, _) in entry.path.match_indices('/') {
+            *totals.entry(entry.path[..slash].to_owned()).or_default() += entry.size.unwrap_or(0);
+        }
+    }
+    let size = |entry: &ListEntry| match entry.is_directory {
+        true => totals.get(&entry.path).copied().unwrap_or(0),
+        false => entry.size.unwrap_or(0),
+    };
+    let date = |entry: &ListEntry| DateTime::parse_from_rfc2822(&entry.updated_at).ok();
+    list.sort_by(|a, b| {
+        let ordering = match by {
+            ListSort::Name => a.path.cmp(&b.path),
+            ListSort::Size => size(a).cmp(&size(b)),
+            ListSort::Date => date(a).cmp(&date(b)),
+        };
+        let ordering = ordering.then_with(|| a.path.cmp(&b.path));
+        match reverse {
+            true => ordering.reverse(),
+            false => ordering,
+        }
+    });
+}
+
+/// Keep the entries of a listing selected by `--filter` and `--depth`. With `--tree`, the
+/// directories above the selected entries are kept too, so that they have somewhere to go.
+fn filter(list: Vec<ListEntry>, args: &ListArgs) -> Result<Vec<ListEntry>> {
+    let filter = PathFilter::new(&args.filter, &[])?;
+    let depth = args.depth.map_or(usize::MAX, |depth| depth as usize);
+    let selected: Vec<bool> = list
+        .iter()
+        .map(|entry| filter.is_selected(&entry.path) && entry.path.split('/').count() <= depth)
+        .collect();
+    let ancestors: HashSet<&str> = match args.tree {
+        true => list
+            .iter()
+            .zip(&selected)
+            .filter(|(_, selected)| **selected)
+            .flat_map(|(entry, _)| entry.path.match_indices('/').map(|(i, _)| &entry.path[..i]))
+            .collect(),
+        false => HashSet::new(),
+    };
+    let keep: Vec<bool> = list
+        .iter()
+        .zip(&selected)
+        .map(|(entry, selected)| *selected || ancestors.contains(entry.path.as_str()))
+        .collect();
+    Ok(list
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(entry, keep)| keep.then_some(entry))
+        .collect())
+}
+
+/// Print a sorted listing as a tree, each entry under its directory, keeping the order of the
+/// entries within each directory.
+fn print_tree(list: &[ListEntry], long: bool) {
+    let mut children: HashMap<&str, Vec<&ListEntry>> = HashMap::new();
+    for entry in list {
+        let parent = entry.path.rsplit_once('/').map_or("", |(parent, _)| parent);
+        children.entry(parent).or_default().push(entry);
+    }
+    fn print_children(
+        dir: &str,
+        depth: usize,
+        children: &HashMap<&str, Vec<&ListEntry>>,
+        long: bool,
+    ) {
+        for entry in children.get(dir).into_iter().flatten() {
+            let name = entry.path.rsplit('/').next().unwrap_or_default();
+            let indent = "  ".repeat(depth);
+            if entry.is_directory {
+                let name = style::directory(format!("{}/", name));
+                print_row(entry, &format!("{}{}", indent, name), long);
+                print_children(&entry.path, depth + 1, children, long);
+            } else {
+                print_row(entry, &format!("{}{}", indent, name), long);
+            }
+        }
+    }
+    print_children("", 0, &children, long);
+}
+
+/// Print an entry of the listing as a row of the table, with its size and, with `--long`, the
+/// time of its last update and its hash.
+fn print_row(entry: &ListEntry, path: &str, long: bool) {
+    let size = entry.size.map(|size| ByteSize(size).to_string());
+    let size = size.unwrap_or_default();
+    if long {
+        let updated = match DateTime::parse_from_rfc2822(&entry.updated_at) {
+            Ok(date) => date.format("%Y-%m-%d %H:%M").to_string(),
+            Err(_) => entry.updated_at.clone(),
+        };
+        let sha1 = entry.sha1_hash.as_deref().unwrap_or_default();
+        println!("{:>10}  {:16}  {:40}  {}", size, updated, sha1, path);
+    } else {
+        println!("{:>10}  {}", size, path);
+    }
+}
+
+/// Convert a listing entry to a JSON record.
+fn to_json(entry: &ListEntry) -> Value {
+    json!({
+        "path": entry.path,
+        "is_directory": entry.is_directory,
+        "size": entry.size,
+        "sha1_hash": entry.sha1_hash,
+        "updated_at": entry.updated_at,
+    })
+}
@@ -0,0 +1,147 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+//! Caching the list of files on a site for a short while, so that consecutive commands (say,
+//! `plan` and then `deploy`) don’t have to fetch it again.
+
+use crate::params::{Config, Params, Site};
+use anyhow::Result;
+use chrono::Utc;
+use neocities_client::response::ListEntry;
+use neocities_client::Client;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fs;
+use std::path::PathBuf;
+
+/// How long a cached list stays valid, in seconds.
+pub const MAX_AGE: i64 = 300;
+
+/// List of files on a site, as saved in the cache directory.
+///
+/// The entries borrow their strings from the list being saved, and the strings of a loaded list
+/// are moved into its entries, so that the list of a large site is never copied.
+#[derive(Serialize, Deserialize)]
+struct Listing<'a> {
+    /// When the list was fetched, in seconds since the Unix epoch.
+    listed_at: i64,
+    /// Where the site the list was fetched from was configured.
+    #[serde(flatten)]
+    source: Source,
+    /// Entries of the list.
+    files: Vec<CachedEntry<'a>>,
+}
+
+/// Where a site is configured, since site names are only unique within a configuration file,
+/// and the same name may be used for sites on different servers.
+#[derive(PartialEq, Serialize, Deserialize)]
+struct Source {
+    /// Configuration file the site is defined in.
+    config: PathBuf,
+    /// Base URL of the API, if not the Neocities one.
+    api_url: Option<String>,
+}
+
+/// An entry of the list, with the fields of [`ListEntry`].
+#[derive(Serialize, Deserialize)]
+struct CachedEntry<'a> {
+    path: Cow<'a, str>,
+    is_directory: bool,
+    updated_at: Cow<'a, str>,
+    size: Option<u64>,
+    sha1_hash: Option<Cow<'a, str>>,
+}
+
+/// List the files on a site, reusing the list fetched by a previous command if it is recent
+/// enough and fetched for the same site, unless `--refresh` is given. The list fetched is cached in
+/// turn.
+pub fn list(params: &Params, name: &str, site: &Site, client: &Client) -> Result<Vec<ListEntry>> {
+    let source = Source {
+        config: params.config_file(),
+        api_url: site.api_base_url(),
+    };
+    if !params.refresh {
+        if let Some(files) = load(name, &source) {
+            tracing::debug!("Using the cached list of files of site {}", name);
+            return Ok(files);
+        }
+    }
+    let files = client.list()?;
+    if let Err(e) = save(name, source, &files) {
+        tracing::debug!("Failed to cache the list of files of site {}: {}", name, e);
+    }
+    Ok(files)
+}
+
+/// Forget the cached list of files of a site, before changing them.
+pub fn invalidate(site: &str) {
+    let path = file(site);
+    if path.exists() {
+        if let Err(e) = fs::remove_file(&path) {
+            tracing::warn!("Failed to remove {:?}: {}", path, e);
+        }
+    }
+}
+
+/// Load the cached list of files of a site, if there is a recent enough one for the same site.
+fn load(site: &str, source: &Source) -> Option<Vec<ListEntry>> {
+    let listing: Listing = serde_json::from_str(&fs::read_to_string(file(site)).ok()?).ok()?;
+    let age = Utc::now().timestamp() - listing.listed_at;
+    if !(0..MAX_AGE).contains(&age) || listing.source != *source {
+        return None;
+    }
+    let files = listing.files.into_iter().map(|e| ListEntry {
+        path: e.path.into_owned(),
+        is_directory: e.is_directory,
+        updated_at: e.updated_at.into_owned(),
+        size: e.size,
+        sha1_hash: e.sha1_hash.map(Cow::into_owned),
+    });
+    Some(files.collect())
+}
+
+/// Save the list of files of a site to the cache.
+fn save(site: &str, source: Source, files: &[ListEntry]) -> Result<()> {
+    let listing = Listing {
+        listed_at: Utc::now().timestamp(),
+        source,
+        files: files
+            .iter()
+            .map(|e| CachedEntry {
+                path: Cow::Borrowed(&e.path),
+                is_directory: e.is_directory,
+                updated_at: Cow::Borrowed(&e.updated_at),
+                size: e.size,
+                sha1_hash: e.sha1_hash.as_deref().map(Cow::Borrowed),
+            })
+            .collect(),
+    };
+    let path = file(site);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Write to a temporary file first, so that an interruption never leaves a truncated file.
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string(&listing)?)?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+/// Path of the cached list of files of a site.
+pub fn file(site: &str) -> PathBuf {
+    Config::site_cache_file("listings", site, "json")
+}
@@ -0,0 +1,66 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Advisory locks that keep two deploys of the same site from running at once.
+
+use crate::params::Config;
+use anyhow::{bail, Result};
+use fs2::FileExt;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::process;
+
+/// Lock on a site, held until it is dropped.
+///
+/// The lock is taken on a file in the cache directory, so it only guards against deploys from
+/// the same machine and user; the operating system releases it if the process dies.
+#[derive(Debug)]
+pub struct SiteLock {
+    _file: File,
+}
+
+impl SiteLock {
+    /// Lock a site, failing at once if another process holds the lock.
+    pub fn acquire(site: &str) -> Result<Self> {
+        let path = Config::site_cache_file("locks", site, "lock");
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        if let Err(e) = file.try_lock_exclusive() {
+            if e.kind() != fs2::lock_contended_error().kind() {
+                return Err(e.into());
+            }
+            let holder = fs::read_to_string(&path).unwrap_or_default();
+            bail!(
+                "Another deploy of site {} is in progress (process {})",
+                site,
+                holder.trim()
+            );
+        }
+        tracing::debug!("Locked site {} with {:?}", site, path);
+        file.set_len(0)?;
+        write!(file, "{}", process::id())?;
+        Ok(Self { _file: file })
+    }
+}
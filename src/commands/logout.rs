@@ -0,0 +1,67 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+use super::config::sites_table;
+use crate::params::{Config, LogoutArgs, Params};
+use anyhow::{anyhow, Result};
+use toml_edit::Item;
+
+/// Remove the stored credentials of a site (its `auth` and `auth_cmd`) from the config file, or,
+/// with `--remove-site`, the whole site.
+///
+/// The credentials are only forgotten locally: the API key keeps working until a new one is
+/// generated on Neocities, which the user is reminded of.
+pub fn logout(params: &Params, args: &LogoutArgs) -> Result<()> {
+    let name = &args.name;
+    let mut removed = false;
+    Config::edit(params.config_file(), |document| {
+        let sites = sites_table(document)?;
+        let not_found = || anyhow!("Site not found: {}", name);
+        if args.remove_site {
+            sites.remove(name).ok_or_else(not_found)?;
+            removed = true;
+            // Keep the `[site]` table even if it is now empty, since it is required.
+            if let Some(sites) = document.get_mut("site").and_then(Item::as_table_mut) {
+                sites.set_implicit(false);
+            }
+            return Ok(());
+        }
+        let site = sites
+            .get_mut(name)
+            .and_then(|site| site.as_table_like_mut())
+            .ok_or_else(not_found)?;
+        for key in ["auth", "auth_cmd"] {
+            removed |= site.remove(key).is_some();
+        }
+        Ok(())
+    })?;
+    match (args.remove_site, removed) {
+        (true, _) => eprintln!("Site {} removed", name),
+        (false, true) => eprintln!("Credentials of site {} removed", name),
+        (false, false) => {
+            eprintln!("Site {} has no stored credentials", name);
+            return Ok(());
+        }
+    }
+    eprintln!(
+        "The credentials were only removed from this computer. To revoke the API key, generate a \
+         new one in the settings of the site on https://neocities.org (and change the password, \
+         if it was stored)."
+    );
+    Ok(())
+}
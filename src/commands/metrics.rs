@@ -0,0 +1,185 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Metrics of deploys, written for the textfile collector of the Prometheus node exporter.
+
+use crate::executor::Summary;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Results of the deploys of a run, one per site.
+#[derive(Debug, Default)]
+pub struct Metrics(Mutex<Vec<Run>>);
+
+/// Result of the deploy of a site.
+#[derive(Debug)]
+struct Run {
+    site: String,
+    /// Counts of the actions, if the deploy succeeded.
+    summary: Option<Summary>,
+    duration: Duration,
+    /// When the deploy finished, in seconds since the Unix epoch.
+    finished: i64,
+}
+
+/// Name, type and help of each metric.
+const METRICS: &[(&str, &str, &str)] = &[
+    (
+        "neocities_deploy_success",
+        "gauge",
+        "Whether the last deploy of the site succeeded.",
+    ),
+    (
+        "neocities_deploy_duration_seconds",
+        "gauge",
+        "How long the last deploy of the site took.",
+    ),
+    (
+        "neocities_deploy_last_run_timestamp_seconds",
+        "gauge",
+        "When the last deploy of the site finished.",
+    ),
+    (
+        "neocities_deploy_files_uploaded",
+        "gauge",
+        "Files uploaded by the last successful deploy of the site.",
+    ),
+    (
+        "neocities_deploy_files_deleted",
+        "gauge",
+        "Files deleted by the last successful deploy of the site.",
+    ),
+    (
+        "neocities_deploy_bytes_uploaded",
+        "gauge",
+        "Bytes uploaded by the last successful deploy of the site.",
+    ),
+    (
+        "neocities_deploy_failed_actions",
+        "gauge",
+        "Actions that failed in the last successful deploy of the site (with --ignore-errors).",
+    ),
+];
+
+impl Metrics {
+    /// Run the deploy of a site, recording how long it took and what came out of it.
+    pub fn measure(&self, site: &str, deploy: impl FnOnce() -> Result<Summary>) -> Result<Summary> {
+        let started = Instant::now();
+        let result = deploy();
+        let run = Run {
+            site: site.to_owned(),
+            summary: result.as_ref().ok().cloned(),
+            duration: started.elapsed(),
+            finished: Utc::now().timestamp(),
+        };
+        self.0.lock().unwrap().push(run);
+        result
+    }
+
+    /// Render the metrics in the Prometheus text format.
+    fn render(&self) -> String {
+        let runs = self.0.lock().unwrap();
+        let mut out = String::new();
+        for (name, kind, help) in METRICS {
+            let values: Vec<_> = runs
+                .iter()
+                .filter_map(|run| Some((&run.site, run.value(name)?)))
+                .collect();
+            if values.is_empty() {
+                continue;
+            }
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (site, value) in values {
+                let _ = writeln!(out, "{}{{site=\"{}\"}} {}", name, escape(site), value);
+            }
+        }
+        out
+    }
+
+    /// Write the metrics to a file, replacing it at once so that the collector never reads a
+    /// partial file.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        fs::write(&temp, self.render())
+            .and_then(|()| fs::rename(&temp, path))
+            .with_context(|| format!("Could not write the metrics to {:?}", path))
+    }
+}
+
+impl Run {
+    /// Value of a metric for this deploy, if it has one.
+    fn value(&self, name: &str) -> Option<String> {
+        let summary = self.summary.as_ref();
+        Some(match name {
+            "neocities_deploy_success" => u8::from(summary.is_some()).to_string(),
+            "neocities_deploy_duration_seconds" => self.duration.as_secs_f64().to_string(),
+            "neocities_deploy_last_run_timestamp_seconds" => self.finished.to_string(),
+            "neocities_deploy_files_uploaded" => summary?.uploaded.to_string(),
+            "neocities_deploy_files_deleted" => summary?.deleted.to_string(),
+            "neocities_deploy_bytes_uploaded" => summary?.bytes.to_string(),
+            "neocities_deploy_failed_actions" => summary?.failed.to_string(),
+            _ => return None,
+        })
+    }
+}
+
+/// Escape the value of a label.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        let summary = Summary {
+            uploaded: 2,
+            deleted: 1,
+            bytes: 300,
+            failed: 0,
+        };
+        metrics.measure("lorem.com", || Ok(summary)).unwrap();
+        metrics
+            .measure("ipsum\"com", || Err(anyhow!("failed")))
+            .unwrap_err();
+        let rendered = metrics.render();
+        assert!(rendered.contains(
+            "# TYPE neocities_deploy_success gauge\n\
+             neocities_deploy_success{site=\"lorem.com\"} 1\n\
+             neocities_deploy_success{site=\"ipsum\\\"com\"} 0\n"
+        ));
+        assert!(rendered.contains("neocities_deploy_files_uploaded{site=\"lorem.com\"} 2\n"));
+        assert!(rendered.contains("neocities_deploy_bytes_uploaded{site=\"lorem.com\"} 300\n"));
+        assert!(!rendered.contains("neocities_deploy_files_uploaded{site=\"ipsum"));
+        assert!(rendered.contains("neocities_deploy_duration_seconds{site=\"ipsum\\\"com\"} "));
+    }
+}
@@ -16,12 +16,55 @@
 ////////       You should have received a copy of the GNU General Public License           ////////
 ////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
 
+mod account;
+mod apply;
+mod check_links;
+mod completions;
 mod config;
 mod deploy;
+mod diff;
+mod doctor;
+mod fingerprint;
+mod first_run;
+mod history;
+mod info;
+mod init;
+mod journal;
 mod key;
 mod list;
+mod listing;
+mod lock;
+mod logout;
+mod metrics;
+mod open;
+mod plan;
+mod preflight;
+mod promote;
+mod report;
+mod rollback;
+mod snapshot;
+mod verify;
+mod watch;
 
+pub use apply::apply;
+pub use check_links::check_links;
+pub use completions::{complete, completions};
 pub use config::config;
 pub use deploy::deploy;
-pub use key::key;
+pub use diff::diff;
+pub use doctor::doctor;
+pub use first_run::ensure_config;
+pub use history::history;
+pub use info::info;
+pub use init::init;
+pub use key::{key, offer_api_keys};
 pub use list::list;
+pub use logout::logout;
+pub use open::open;
+pub use plan::plan;
+pub use promote::promote;
+pub use report::report;
+pub use rollback::rollback;
+pub use snapshot::snapshot;
+pub use verify::verify;
+pub use watch::watch;
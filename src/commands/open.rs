@@ -0,0 +1,56 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+use crate::params::{OpenArgs, Params};
+use anyhow::{bail, Result};
+use std::process::Command;
+
+/// Open the site(s) in the web browser.
+pub fn open(params: &Params, args: &OpenArgs) -> Result<()> {
+    for (name, site) in params.sites()? {
+        let mut url = site.public_url()?;
+        if let Some(path) = &args.path {
+            url = url.join(path.trim_start_matches('/'))?;
+        }
+        if args.print {
+            println!("{}", url);
+        } else {
+            tracing::info!("Opening site {} at {}", name, url);
+            browse(url.as_str())?;
+        }
+    }
+    Ok(())
+}
+
+/// Open a URL with the system's default web browser.
+fn browse(url: &str) -> Result<()> {
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
+    let status = command.arg(url).status()?;
+    if !status.success() {
+        bail!("Failed to open {} in the browser ({})", url, status);
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+use super::account;
+use super::deploy::plan_site;
+use crate::params::{Params, PlanArgs};
+use crate::plan::Action;
+use crate::style;
+use anyhow::{bail, Result};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Version of the plan file format.
+const PLAN_VERSION: u32 = 1;
+
+/// A saved deploy plan: the actions to perform on each site, in order.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlanFile {
+    /// Version of the file format.
+    pub version: u32,
+    /// Actions for each site.
+    pub sites: IndexMap<String, Vec<Action>>,
+}
+
+impl PlanFile {
+    /// Load a plan from a file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let plan: Self = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if plan.version != PLAN_VERSION {
+            bail!("Unsupported plan version: {}", plan.version);
+        }
+        Ok(plan)
+    }
+}
+
+/// Compute the actions needed to deploy the site(s) and save them to a plan file.
+pub fn plan(params: &Params, args: &PlanArgs) -> Result<()> {
+    let mut plan = PlanFile {
+        version: PLAN_VERSION,
+        sites: IndexMap::new(),
+    };
+    for (name, site) in params.sites()? {
+        let client = site.build_client()?;
+        let site = account::settle(params, &name, &site, &client, &args.plan)?;
+        let actions = plan_site(params, &name, &args.plan, &site, &client)?;
+        eprintln!("Site {}: {} action(s)", name, actions.len());
+        for action in &actions {
+            eprintln!("  {}", style::action(action.kind(), action));
+        }
+        plan.sites.insert(name, actions);
+    }
+    let json = serde_json::to_string_pretty(&plan)?;
+    match &args.output {
+        Some(output) => {
+            fs::write(output, json)?;
+            tracing::info!("Plan saved to {:?}", output);
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
@@ -0,0 +1,491 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Checks run on the local files before a deploy, to catch problems that would otherwise only
+//! show up as missing content or rejected requests.
+
+use super::account;
+use super::check_links::broken_links;
+use super::fingerprint;
+use crate::params::{PlanningArgs, Site};
+use crate::plan::Action;
+use crate::trees::{self, Entry};
+use crate::{ci, images};
+use anyhow::{bail, Result};
+use bytesize::ByteSize;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use unicode_normalization::UnicodeNormalization;
+
+/// Turn the local tree of a site into the files a deploy uploads: leave out the precompressed
+/// copies and the files the account cannot upload (failing instead with `strict`), deal with the
+/// empty directories, recompress the images and fingerprint the assets.
+pub fn prepare_local_tree(local: &mut Vec<Entry>, site: &Site, strict: bool) -> Result<()> {
+    skip_precompressed(local, site);
+    empty_dirs(local, site);
+    check_extensions(local, site, strict)?;
+    images::optimize(local, site)?;
+    fingerprint::apply(local, site)
+}
+
+/// Put back the contents of the uploads that [`prepare_local_tree`] rewrote (optimized images,
+/// and files with fingerprinted references), which are not saved with the actions in journals and
+/// plan files, by preparing the local tree again. Uploads of files that no longer get the same
+/// contents are left reading the local file, so their hashes tell that they have changed.
+pub fn restore_content(site: &Site, actions: &mut [Action]) -> Result<()> {
+    let rewrites = site.optimize_images.is_some()
+        || site
+            .fingerprint
+            .as_ref()
+            .is_some_and(|globs| !globs.is_empty());
+    let missing = |action: &Action| matches!(action, Action::Upload(entry) if entry.content.is_none() && entry.local_path.is_some());
+    if !rewrites || !actions.iter().any(missing) {
+        return Ok(());
+    }
+    let mut local = trees::merged_tree(&site.source_dirs(), false, &site.tree_options())?;
+    prepare_local_tree(&mut local, site, false)?;
+    let contents: HashMap<String, Arc<[u8]>> = local
+        .into_iter()
+        .filter_map(|entry| Some((entry.path, entry.content?)))
+        .collect();
+    for action in actions.iter_mut() {
+        let Action::Upload(entry) = action else {
+            continue;
+        };
+        let Some(content) = contents
+            .get(&entry.path)
+            .filter(|_| entry.content.is_none())
+        else {
+            continue;
+        };
+        let planned = entry.info.as_ref().map(|info| info.sha1_sum.as_str());
+        // Plans made with `--since` do not hash the files.
+        if planned
+            .is_none_or(|sha1_sum| sha1_sum.is_empty() || sha1_sum == trees::sha1_bytes(content))
+        {
+            entry.content = Some(content.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Make sure the local directory of a site exists, creating it with `create`, or if the user
+/// agrees when running interactively. Returns whether the deploy can go on: a directory just
+/// created is empty, and deploying it would delete every file of the site.
+pub fn ensure_local_path(name: &str, site: &Site, create: bool) -> Result<bool> {
+    let path = Path::new(&site.path);
+    if path.is_dir() {
+        return Ok(true);
+    } else if path.exists() {
+        bail!(
+            "The local path of site {}, {:?}, is not a directory",
+            name,
+            path
+        );
+    }
+    let create = create
+        || ci::interactive()
+            && inquire::Confirm::new(&format!(
+                "The local directory of site {}, {:?}, does not exist. Create it?",
+                name, path
+            ))
+            .with_default(false)
+            .prompt()?;
+    if !create {
+        bail!(
+            "The local directory of site {}, {:?}, does not exist; use `--create-path` to create it",
+            name,
+            path
+        );
+    }
+    fs::create_dir_all(path)?;
+    tracing::warn!(
+        "Created the local directory {:?}; put the files of site {} there and deploy again",
+        path,
+        name
+    );
+    Ok(false)
+}
+
+/// Extensions of the compressed copies of files that static site generators write next to them,
+/// for web servers that serve them to the browsers that accept them. Neocities doesn’t.
+const PRECOMPRESSED_EXTENSIONS: &[&str] = &[".gz", ".br"];
+
+/// Remove the compressed copies of files (like `app.js.gz` next to `app.js`) from the local tree,
+/// reporting each of them, unless the site sets `skip_precompressed` to `false`.
+pub fn skip_precompressed(local: &mut Vec<Entry>, site: &Site) {
+    if !site.skip_precompressed.unwrap_or(true) {
+        return;
+    }
+    let files: HashSet<String> = local
+        .iter()
+        .filter(|entry| entry.is_file())
+        .map(|entry| entry.path.clone())
+        .collect();
+    let is_precompressed = |path: &str| {
+        PRECOMPRESSED_EXTENSIONS.iter().any(|extension| {
+            path.strip_suffix(extension)
+                .is_some_and(|original| files.contains(original))
+        })
+    };
+    let skipped = trees::remove_disallowed(local, |path| !is_precompressed(path));
+    if skipped.is_empty() {
+        return;
+    }
+    tracing::warn!(
+        "{} precompressed file(s) skipped, since the files they compress are deployed:",
+        skipped.len()
+    );
+    for entry in &skipped {
+        tracing::warn!("  {}", entry.path);
+    }
+}
+
+/// Name of the placeholder file put in empty directories with `keep_empty_dirs`, with an extension
+/// that free accounts can upload.
+const KEEP_FILE: &str = ".keep.txt";
+
+/// Deal with the empty local directories, which the API cannot create: add an empty placeholder
+/// file to each of them if the site sets `keep_empty_dirs`, or else report them, since they won’t
+/// show up on the site.
+pub fn empty_dirs(local: &mut Vec<Entry>, site: &Site) {
+    let parents: HashSet<&str> = local
+        .iter()
+        .filter_map(|entry| entry.path.rsplit_once('/').map(|(parent, _)| parent))
+        .collect();
+    let empty: Vec<String> = local
+        .iter()
+        .filter(|entry| !entry.is_file() && !parents.contains(entry.path.as_str()))
+        .map(|entry| entry.path.clone())
+        .collect();
+    if empty.is_empty() {
+        return;
+    }
+    if !site.keep_empty_dirs.unwrap_or_default() {
+        tracing::warn!(
+            "{} empty directory(ies) will not be created on the site (set `keep_empty_dirs` to \
+             keep them):",
+            empty.len()
+        );
+        for path in &empty {
+            tracing::warn!("  {}", path);
+        }
+        return;
+    }
+    tracing::debug!(
+        "Adding {} to {} empty directory(ies)",
+        KEEP_FILE,
+        empty.len()
+    );
+    local.extend(empty.into_iter().map(|path| Entry {
+        path: format!("{}/{}", path, KEEP_FILE),
+        info: Some(trees::FileInfo {
+            size: 0,
+            sha1_sum: trees::sha1_bytes(&[]),
+            modified: None,
+        }),
+        local_path: None,
+        content: Some(Vec::new().into()),
+    }));
+    local.sort_by(|a, b| a.path.cmp(&b.path));
+}
+
+/// Remove the files a free account cannot upload from the local tree, reporting each of them
+/// with a suggestion. With `strict`, fail if there are any. When the account type is unknown,
+/// nothing is removed, but a warning tells how many files it would be.
+pub fn check_extensions(local: &mut Vec<Entry>, site: &Site, strict: bool) -> Result<()> {
+    if site.free_account.is_none() {
+        let free = Site {
+            free_account: Some(true),
+            ..site.clone()
+        };
+        let count = local
+            .iter()
+            .filter(|entry| entry.is_file() && !free.is_allowed(&entry.path))
+            .count();
+        if count > 0 {
+            tracing::warn!(
+                "{} file(s) cannot be uploaded if the account is free, and the site doesn’t say \
+                 whether it is; run `doctor`, or pass `--detect-account`, to find out",
+                count
+            );
+        }
+    }
+    let disallowed = trees::remove_disallowed(local, |path| site.is_allowed(path));
+    if disallowed.is_empty() {
+        return Ok(());
+    }
+    tracing::warn!(
+        "{} file(s) skipped, because free accounts cannot upload them:",
+        disallowed.len()
+    );
+    for entry in &disallowed {
+        tracing::warn!("  {} ({})", entry.path, suggest_fix(&entry.path));
+    }
+    if strict {
+        bail!(
+            "{} file(s) cannot be uploaded with a free account",
+            disallowed.len()
+        );
+    }
+    Ok(())
+}
+
+/// Check the sizes of the local files against the account quota, the largest file the account can
+/// upload, and the limits given with `--max-total-size` and `--max-file-size`.
+///
+/// Going over the quota is only a warning, unless `strict` is set, since the quota might have
+/// been raised; going over the other limits is always an error, since Neocities would reject the
+/// files in the middle of the deploy.
+pub fn check_sizes(local: &[Entry], site: &Site, args: &PlanningArgs) -> Result<()> {
+    let files = local
+        .iter()
+        .filter_map(|e| Some((e, e.info.as_ref()?.size)));
+    let total = ByteSize::b(files.clone().map(|(_, size)| size).sum());
+    let count = files.clone().count();
+    tracing::debug!("Local tree: {} file(s), {}", count, total);
+
+    let (quota, file_limit, kind) = account::limits(site);
+    if total > quota {
+        let message = format!(
+            "The local files add up to {}, more than the {} GB quota of {} accounts",
+            total,
+            quota.as_u64() / ByteSize::gb(1).as_u64(),
+            kind
+        );
+        if args.strict {
+            bail!(message);
+        }
+        tracing::warn!("{}", message);
+    }
+    if let Some(max) = args.max_total_size {
+        if total > max {
+            bail!(
+                "The local files add up to {}, more than --max-total-size {}",
+                total,
+                max
+            );
+        }
+    }
+    let limit = format!("the {} limit of {} accounts", file_limit, kind);
+    check_file_sizes(files.clone(), file_limit, &limit)?;
+    if let Some(max) = args.max_file_size {
+        check_file_sizes(files, max, &format!("--max-file-size {}", max))?;
+    }
+    Ok(())
+}
+
+/// Fail if any of the files is larger than a limit, listing them.
+fn check_file_sizes<'a>(
+    files: impl Iterator<Item = (&'a Entry, u64)>,
+    max: ByteSize,
+    limit: &str,
+) -> Result<()> {
+    let large: Vec<_> = files.filter(|(_, size)| *size > max.as_u64()).collect();
+    for (entry, size) in &large {
+        tracing::error!("  {} ({})", entry.path, ByteSize::b(*size));
+    }
+    if !large.is_empty() {
+        bail!("{} file(s) are larger than {}", large.len(), limit);
+    }
+    Ok(())
+}
+
+/// Fail if two local paths would end up as the same path on the site, because they differ only in
+/// case or in Unicode normalization (e.g. `About.html` and `about.html`, or `café` written with a
+/// precomposed `é` and with `e` plus a combining accent).
+pub fn check_collisions(local: &[Entry]) -> Result<()> {
+    let mut groups: IndexMap<String, Vec<&str>> = IndexMap::new();
+    for entry in local {
+        let key = entry.path.nfc().collect::<String>().to_lowercase();
+        groups.entry(key).or_default().push(&entry.path);
+    }
+    let collisions: Vec<_> = groups.values().filter(|paths| paths.len() > 1).collect();
+    if collisions.is_empty() {
+        return Ok(());
+    }
+    for paths in &collisions {
+        tracing::error!("  {}", paths.iter().map(|p| format!("{:?}", p)).join(", "));
+    }
+    bail!(
+        "{} group(s) of local paths differ only in case or Unicode normalization, and would \
+         collide on the site",
+        collisions.len()
+    );
+}
+
+/// Suggest what to do about a file with an extension that free accounts cannot upload.
+fn suggest_fix(path: &str) -> String {
+    let extension = Path::new(path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+    let Some(extension) = extension else {
+        return "no extension; rename it to add `.txt`".to_owned();
+    };
+    let rename = match extension.as_str() {
+        "jpe" | "jfif" | "pjpeg" | "pjp" => "jpg",
+        "mjs" | "cjs" => "js",
+        "xhtml" | "shtml" | "xht" => "html",
+        "jsonld" | "jsonc" | "har" => "json",
+        "conf" | "cfg" | "ini" | "log" | "nfo" => "txt",
+        "xsl" | "xslt" | "xsd" => "xml",
+        _ => return format!("`.{}` files need a supporter account", extension),
+    };
+    format!("rename it to `.{}`", rename)
+}
+
+/// With `--check-links`, fail if the relative links of the local HTML files point to files that
+/// are not in the tree.
+pub fn check_links(local: &[Entry], args: &PlanningArgs) -> Result<()> {
+    if !args.check_links {
+        return Ok(());
+    }
+    let broken = broken_links(local)?;
+    for link in &broken {
+        tracing::error!("  Broken link in {}", link);
+    }
+    if !broken.is_empty() {
+        bail!("{} broken link(s) found", broken.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str) -> Entry {
+        Entry {
+            path: path.to_owned(),
+            info: None,
+            local_path: None,
+            content: None,
+        }
+    }
+
+    fn file(path: &str) -> Entry {
+        Entry {
+            info: Some(trees::FileInfo {
+                size: 0,
+                sha1_sum: String::new(),
+                modified: None,
+            }),
+            ..entry(path)
+        }
+    }
+
+    #[test]
+    fn test_empty_dirs() {
+        let tree = || {
+            vec![
+                entry("a"),
+                entry("a/b"),
+                file("a/b/c.txt"),
+                entry("a/empty"),
+                entry("empty"),
+                file("index.html"),
+            ]
+        };
+        let paths = |local: &[Entry]| -> Vec<String> {
+            local.iter().map(|entry| entry.path.clone()).collect()
+        };
+        let mut site: Site = toml::from_str("path = '.'").unwrap();
+        let mut local = tree();
+        empty_dirs(&mut local, &site);
+        assert_eq!(paths(&local), paths(&tree()));
+
+        site.keep_empty_dirs = Some(true);
+        empty_dirs(&mut local, &site);
+        assert_eq!(
+            paths(&local),
+            [
+                "a",
+                "a/b",
+                "a/b/c.txt",
+                "a/empty",
+                "a/empty/.keep.txt",
+                "empty",
+                "empty/.keep.txt",
+                "index.html"
+            ]
+        );
+        assert!(local[4].is_file());
+        assert_eq!(local[4].info.as_ref().unwrap().size, 0);
+
+        // Free accounts can upload the placeholders too.
+        site.free_account = Some(true);
+        let mut local = tree();
+        prepare_local_tree(&mut local, &site, true).unwrap();
+        assert!(paths(&local).contains(&"empty/.keep.txt".to_owned()));
+    }
+
+    #[test]
+    fn test_skip_precompressed() {
+        let mut local = vec![
+            file("app.js"),
+            file("app.js.br"),
+            file("app.js.gz"),
+            entry("docs"),
+            file("docs/manual.pdf.gz"),
+            file("style.css.gz"),
+        ];
+        let site = Site::new(neocities_client::Auth::from("key"), "");
+        skip_precompressed(&mut local, &site);
+        let paths: Vec<_> = local.iter().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["app.js", "docs", "docs/manual.pdf.gz", "style.css.gz"]
+        );
+
+        let mut local = vec![file("app.js"), file("app.js.gz")];
+        let site = Site {
+            skip_precompressed: Some(false),
+            ..site
+        };
+        skip_precompressed(&mut local, &site);
+        assert_eq!(local.len(), 2);
+    }
+
+    #[test]
+    fn test_check_collisions() {
+        let local = [entry("about.html"), entry("caf\u{e9}"), entry("index.html")];
+        assert!(check_collisions(&local).is_ok());
+        let local = [entry("About.html"), entry("about.html")];
+        assert!(check_collisions(&local).is_err());
+        let local = [entry("caf\u{e9}"), entry("cafe\u{301}")];
+        assert!(check_collisions(&local).is_err());
+    }
+
+    #[test]
+    fn test_suggest_fix() {
+        assert_eq!(suggest_fix("photo.JFIF"), "rename it to `.jpg`");
+        assert_eq!(suggest_fix("lib/app.mjs"), "rename it to `.js`");
+        assert_eq!(
+            suggest_fix("CNAME"),
+            "no extension; rename it to add `.txt`"
+        );
+        assert_eq!(
+            suggest_fix("song.mp3"),
+            "`.mp3` files need a supporter account"
+        );
+    }
+}
@@ -0,0 +1,78 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+use super::deploy::execute_actions;
+use super::lock::SiteLock;
+use crate::control::Control;
+use crate::params::{Params, PromoteArgs, Site};
+use crate::plan::{Action, Compare, DeployOrder, Plan};
+use crate::trees;
+use anyhow::{anyhow, bail, Result};
+use neocities_client::Client;
+
+/// Copy the files of the staging site(s) to production.
+pub fn promote(params: &Params, args: &PromoteArgs, control: &Control) -> Result<()> {
+    for (name, site) in params.sites()? {
+        let (staging_name, staging) = site.staging(&name)?;
+        tracing::info!("Promoting {} to {}", staging_name, name);
+        let _lock = SiteLock::acquire(&name)?;
+        let client = site.build_client()?;
+        let actions = plan_promotion(&site, &client, &staging)?;
+        execute_actions(params, &args.exec, &name, &site, &client, actions, control)?;
+    }
+    tracing::info!("Promotion complete");
+    Ok(())
+}
+
+/// Compute the actions that make a site match its staging site.
+///
+/// Only the files that differ are downloaded from the staging site, and each one must match the
+/// hash in the staging site’s listing, so that what goes live is exactly what was staged.
+fn plan_promotion(site: &Site, client: &Client, staging: &Site) -> Result<Vec<Action>> {
+    let prefix = site.remote_prefix();
+    let mut source = trees::remote_tree(&staging.build_client()?.list()?);
+    trees::scope_remote(&mut source, &prefix);
+    let mut remote = trees::remote_tree(&client.list()?);
+    trees::scope_remote(&mut remote, &prefix);
+    for path in trees::protect_remote(&source, &mut remote, site.protection()?) {
+        tracing::debug!("Keeping protected file: {}", path);
+    }
+    let plan = Plan::compare(source, remote, Compare::Hash);
+    let fetch = staging.fetcher()?;
+    plan.reorder(DeployOrder::UploadsFirst, true)
+        .actions
+        .into_iter()
+        .map(|action| match action {
+            Action::Upload(mut entry) => {
+                let content = fetch(&entry.path)?.ok_or_else(|| {
+                    anyhow!("Cannot download {} from the staging site", entry.path)
+                })?;
+                let expected = entry.info.as_ref().map(|info| info.sha1_sum.as_str());
+                if expected != Some(trees::sha1_bytes(&content).as_str()) {
+                    bail!(
+                        "{} on the staging site does not match its listing",
+                        entry.path
+                    );
+                }
+                entry.content = Some(content.into());
+                Ok(Action::Upload(entry))
+            }
+            action => Ok(action),
+        })
+        .collect()
+}
@@ -0,0 +1,93 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+use super::{account, listing};
+use crate::params::{Params, ReportArgs};
+use crate::trees::{self, Entry};
+use anyhow::Result;
+use bytesize::ByteSize;
+use indexmap::IndexMap;
+
+/// Show how much storage the site(s) use.
+pub fn report(params: &Params, args: &ReportArgs) -> Result<()> {
+    for (name, site) in params.sites()? {
+        println!("Storage report for site {}", name);
+        let client = site.build_client()?;
+        let list = listing::list(params, &name, &site, &client).or_else(|e| {
+            if params.ignore_errors {
+                tracing::error!("{}", e);
+                Ok(vec![])
+            } else {
+                Err(e)
+            }
+        })?;
+        let remote = trees::remote_tree(&list);
+
+        let files: Vec<_> = remote.iter().filter(|e| e.is_file()).collect();
+        let total: u64 = files.iter().map(|e| size(e)).sum();
+        let (quota, ..) = account::limits(&site);
+        println!(
+            "Total: {} in {} file(s), {:.1}% of the {} GB quota",
+            ByteSize(total),
+            files.len(),
+            100.0 * total as f64 / quota.as_u64() as f64,
+            quota.as_u64() / ByteSize::gb(1).as_u64(),
+        );
+
+        let directories = directory_sizes(&remote);
+        if !directories.is_empty() {
+            println!("Directories:");
+            for (path, size) in directories.iter().take(args.top) {
+                println!("{:>10}  {}/", ByteSize(*size), path);
+            }
+        }
+
+        let mut largest = files;
+        largest.sort_by_key(|e| std::cmp::Reverse(size(e)));
+        if !largest.is_empty() {
+            println!("Largest files:");
+            for entry in largest.iter().take(args.top) {
+                println!("{:>10}  {}", ByteSize(size(entry)), entry.path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Size of an entry, which is zero for directories.
+fn size(entry: &Entry) -> u64 {
+    entry.info.as_ref().map_or(0, |info| info.size)
+}
+
+/// Compute the total size of the files under each directory, largest first.
+fn directory_sizes(tree: &[Entry]) -> Vec<(String, u64)> {
+    let mut sizes: IndexMap<String, u64> = tree
+        .iter()
+        .filter(|e| !e.is_file())
+        .map(|e| (e.path.clone(), 0))
+        .collect();
+    for entry in tree.iter().filter(|e| e.is_file()) {
+        let mut path = entry.path.as_str();
+        while let Some((parent, _)) = path.rsplit_once('/') {
+            *sizes.entry(parent.to_owned()).or_default() += size(entry);
+            path = parent;
+        }
+    }
+    let mut sizes: Vec<_> = sizes.into_iter().collect();
+    sizes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    sizes
+}
@@ -0,0 +1,184 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Copies of the remote files that a deploy overwrites or deletes, kept in the data directory so
+//! that the deploy can be rolled back.
+
+use super::deploy::execute_actions;
+use super::lock::SiteLock;
+use crate::control::Control;
+use crate::params::{Config, Params, RollbackArgs, Site};
+use crate::plan::{Action, DeployOrder, Plan};
+use crate::trees::{self, Entry, FileInfo};
+use anyhow::{anyhow, bail, Result};
+use chrono::{SecondsFormat, Utc};
+use neocities_client::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+
+/// Put back the files that the last deploy of the site(s) overwrote or deleted.
+pub fn rollback(params: &Params, args: &RollbackArgs, control: &Control) -> Result<()> {
+    for (name, site) in params.sites()? {
+        let Some((manifest, actions)) = load(&name)? else {
+            bail!(
+                "Nothing to roll back for site {}; deploy with --stash first",
+                name
+            );
+        };
+        tracing::info!(
+            "Rolling back the deploy of {} to site {}",
+            manifest.taken_at,
+            name
+        );
+        let _lock = SiteLock::acquire(&name)?;
+        let client = site.build_client()?;
+        let actions = Plan::from(actions).reorder(DeployOrder::UploadsFirst, true);
+        execute_actions(
+            params,
+            &args.exec,
+            &name,
+            &site,
+            &client,
+            actions.actions,
+            control,
+        )?;
+        // When stashing is on, the rollback itself was stashed, and can be rolled back in turn.
+        if !(args.exec.stash || site.stash.unwrap_or_default()) {
+            fs::remove_dir_all(dir(&name))?;
+        }
+    }
+    tracing::info!("Rollback complete");
+    Ok(())
+}
+
+/// What a stash holds.
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    /// When the stashed deploy started, in RFC 3339 format.
+    taken_at: String,
+    /// Remote files that were overwritten or deleted, whose copies are in the stash.
+    restore: BTreeSet<String>,
+    /// Remote files that the deploy created, which a rollback deletes.
+    remove: BTreeSet<String>,
+}
+
+/// Save copies of the remote files that some actions are about to overwrite or delete, replacing
+/// the previous stash of the site. Nothing is saved if there are no actions.
+pub fn stash(name: &str, site: &Site, client: &Client, actions: &[Action]) -> Result<()> {
+    if actions.is_empty() {
+        return Ok(());
+    }
+    let remote = trees::remote_tree(&client.list()?);
+    let files: Vec<_> = remote.iter().filter(|e| e.is_file()).collect();
+    let mut manifest = Manifest {
+        taken_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        restore: BTreeSet::new(),
+        remove: BTreeSet::new(),
+    };
+    for action in actions {
+        let path = &action.entry().path;
+        let prefix = format!("{}/", path);
+        match action {
+            Action::Upload(_) if files.iter().any(|e| &e.path == path) => {
+                manifest.restore.insert(path.clone());
+            }
+            Action::Upload(_) => {
+                manifest.remove.insert(path.clone());
+            }
+            Action::DeleteRemote(_) => {
+                let deleted = files
+                    .iter()
+                    .filter(|e| &e.path == path || e.path.starts_with(&prefix));
+                manifest.restore.extend(deleted.map(|e| e.path.clone()));
+            }
+        }
+    }
+    tracing::info!(
+        "Stashing {} remote file(s) for rollback",
+        manifest.restore.len()
+    );
+    // Build the new stash next to the old one, which is only replaced once it is complete.
+    let dir = dir(name);
+    let tmp = PathBuf::from(format!("{}.tmp", dir.display()));
+    if tmp.exists() {
+        fs::remove_dir_all(&tmp)?;
+    }
+    let fetch = site.fetcher()?;
+    for path in &manifest.restore {
+        if let Some(problem) = trees::path_problem(path) {
+            bail!("Cannot stash {:?}: the path {}", path, problem);
+        }
+        let content =
+            fetch(path)?.ok_or_else(|| anyhow!("Cannot download {} to stash it", path))?;
+        let file = tmp.join("files").join(path);
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(file, content)?;
+    }
+    fs::create_dir_all(&tmp)?;
+    fs::write(
+        tmp.join("manifest.json"),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::rename(&tmp, &dir)?;
+    Ok(())
+}
+
+/// Load the stash of a site, with the actions that roll the deploy back.
+fn load(name: &str) -> Result<Option<(Manifest, Vec<Action>)>> {
+    let dir = dir(name);
+    let path = dir.join("manifest.json");
+    if !path.exists() {
+        return Ok(None);
+    }
+    let manifest: Manifest = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let mut actions = vec![];
+    for path in &manifest.restore {
+        let content = fs::read(dir.join("files").join(path))?;
+        actions.push(Action::Upload(Entry {
+            path: path.clone(),
+            info: Some(FileInfo {
+                size: content.len() as u64,
+                sha1_sum: trees::sha1_bytes(&content),
+                modified: None,
+            }),
+            local_path: None,
+            content: Some(content.into()),
+        }));
+    }
+    for path in &manifest.remove {
+        actions.push(Action::DeleteRemote(Entry {
+            path: path.clone(),
+            info: None,
+            local_path: None,
+            content: None,
+        }));
+    }
+    Ok(Some((manifest, actions)))
+}
+
+/// Directory of the stash of a site.
+pub fn dir(name: &str) -> PathBuf {
+    Config::site_data_file("rollback", name, "")
+}
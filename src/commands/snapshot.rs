@@ -0,0 +1,207 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Snapshots of the files on a site, to notice changes made outside of this tool (e.g. with the
+//! web editor).
+
+use crate::params::{Config, Params, SnapshotAction, SnapshotArgs};
+use anyhow::{bail, Result};
+use chrono::{SecondsFormat, Utc};
+use itertools::{EitherOrBoth::*, Itertools};
+use neocities_client::Client;
+use parse_display::Display;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Save the files on the site(s), or compare them with the saved snapshots.
+pub fn snapshot(params: &Params, args: &SnapshotArgs) -> Result<()> {
+    let mut changed = 0;
+    for (name, site) in params.sites()? {
+        let client = site.build_client()?;
+        match args.action {
+            SnapshotAction::Save => {
+                let snapshot = Snapshot::take(&name, &client)?;
+                snapshot.save()?;
+                println!(
+                    "Saved snapshot of site {} ({} file(s))",
+                    name,
+                    snapshot.files.len()
+                );
+            }
+            SnapshotAction::Diff => {
+                let Some(saved) = Snapshot::load(&name)? else {
+                    bail!("No snapshot of site {}; run `snapshot save` first", name);
+                };
+                let current = Snapshot::take(&name, &client)?;
+                println!("Site {} (snapshot of {})", name, saved.taken_at);
+                let changes = saved.changes(&current);
+                for change in &changes {
+                    println!("{}", change);
+                }
+                changed += changes.len();
+            }
+        }
+    }
+    if changed > 0 {
+        bail!("{} file(s) changed since the snapshot", changed);
+    }
+    Ok(())
+}
+
+/// Update the snapshot of a site after a deploy, if there is one, so that only the changes made
+/// elsewhere show up in `snapshot diff`.
+pub fn refresh(site: &str, client: &Client) -> Result<()> {
+    if Snapshot::file(site).exists() {
+        tracing::debug!("Refreshing the snapshot of site {}", site);
+        Snapshot::take(site, client)?.save()?;
+    }
+    Ok(())
+}
+
+/// The files on a site at some point in time.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Name of the site.
+    pub site: String,
+    /// When the snapshot was taken, in RFC 3339 format.
+    pub taken_at: String,
+    /// Size and hash of each file, by path.
+    pub files: BTreeMap<String, SnapshotFile>,
+}
+
+/// A file in a snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotFile {
+    /// Size of the file in bytes.
+    pub size: u64,
+    /// SHA-1 hash of the file.
+    pub sha1_sum: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Display)]
+/// A change to the files on a site between two snapshots.
+pub enum Change {
+    /// The file was added.
+    #[display("     added  {0}")]
+    Added(String),
+    /// The file was modified.
+    #[display("  modified  {0}")]
+    Modified(String),
+    /// The file was removed.
+    #[display("   removed  {0}")]
+    Removed(String),
+}
+
+impl Snapshot {
+    /// Take a snapshot of the files currently on a site.
+    pub fn take(site: &str, client: &Client) -> Result<Self> {
+        let files = client
+            .list()?
+            .into_iter()
+            .filter(|entry| !entry.is_directory)
+            .map(|entry| {
+                let file = SnapshotFile {
+                    size: entry.size.unwrap_or_default(),
+                    sha1_sum: entry.sha1_hash.unwrap_or_default(),
+                };
+                (entry.path, file)
+            })
+            .collect();
+        Ok(Self {
+            site: site.to_owned(),
+            taken_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            files,
+        })
+    }
+
+    /// Load the saved snapshot of a site, if there is one.
+    pub fn load(site: &str) -> Result<Option<Self>> {
+        let path = Self::file(site);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+    }
+
+    /// Save the snapshot, replacing the previous one of the site.
+    pub fn save(&self) -> Result<()> {
+        let path = Self::file(&self.site);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write to a temporary file first, so that an interruption never leaves a truncated file.
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// Changes from this snapshot to a later one.
+    pub fn changes(&self, later: &Snapshot) -> Vec<Change> {
+        self.files
+            .iter()
+            .merge_join_by(&later.files, |(a, _), (b, _)| a.cmp(b))
+            .filter_map(|pair| match pair {
+                Left((path, _)) => Some(Change::Removed(path.clone())),
+                Right((path, _)) => Some(Change::Added(path.clone())),
+                Both((path, a), (_, b)) if a != b => Some(Change::Modified(path.clone())),
+                Both(..) => None,
+            })
+            .collect()
+    }
+
+    /// Path of the snapshot file for a site.
+    pub fn file(site: &str) -> PathBuf {
+        Config::site_data_file("snapshots", site, "json")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changes() {
+        let snapshot = |files: &[(&str, &str)]| Snapshot {
+            site: "lorem.com".to_owned(),
+            taken_at: String::new(),
+            files: files
+                .iter()
+                .map(|(path, sha1_sum)| {
+                    let file = SnapshotFile {
+                        size: 1,
+                        sha1_sum: sha1_sum.to_string(),
+                    };
+                    (path.to_string(), file)
+                })
+                .collect(),
+        };
+        let before = snapshot(&[("a.html", "1"), ("b.html", "2"), ("c.html", "3")]);
+        let after = snapshot(&[("a.html", "1"), ("b.html", "0"), ("d.html", "4")]);
+        assert_eq!(
+            before.changes(&after),
+            [
+                Change::Modified("b.html".to_owned()),
+                Change::Removed("c.html".to_owned()),
+                Change::Added("d.html".to_owned()),
+            ]
+        );
+    }
+}
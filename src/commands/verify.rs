@@ -0,0 +1,93 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+use super::preflight;
+use crate::params::Params;
+use crate::trees::{self, Entry};
+use anyhow::{bail, Result};
+use itertools::{EitherOrBoth::*, Itertools};
+use parse_display::Display;
+
+/// Check that the files on the site(s) match the local files.
+pub fn verify(params: &Params) -> Result<()> {
+    let mut problems = 0;
+    for (name, site) in params.sites()? {
+        println!("Verifying site {}", name);
+        let mut local = trees::merged_tree(&site.source_dirs(), true, &site.tree_options())?;
+        preflight::prepare_local_tree(&mut local, &site, false)?;
+        let client = site.build_client()?;
+        let list = client.list()?;
+        let mut remote = trees::remote_tree(&list);
+        trees::scope_remote(&mut remote, &site.remote_prefix());
+        let mismatches = compare(local, remote);
+        for mismatch in &mismatches {
+            println!("{}", mismatch);
+        }
+        problems += mismatches.len();
+    }
+    if problems > 0 {
+        bail!(
+            "{} file(s) differ between the local tree and the site",
+            problems
+        );
+    }
+    println!("All files match");
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq, Display)]
+/// A difference between a local file and the corresponding remote file.
+pub enum Mismatch {
+    /// The local file is missing on the remote.
+    #[display("   missing  {0}")]
+    Missing(String),
+    /// The remote file is smaller than the local file, probably because of an interrupted upload.
+    #[display(" truncated  {0}")]
+    Truncated(String),
+    /// The remote file has different contents from the local file.
+    #[display("  modified  {0}")]
+    Modified(String),
+    /// The remote file does not exist locally.
+    #[display("     extra  {0}")]
+    Extra(String),
+}
+
+/// Compare the local and remote trees file by file.
+///
+/// **Note:** Like [`Plan::compare`](crate::plan::Plan::compare), this function assumes that both
+/// trees are sorted.
+pub fn compare(local: Vec<Entry>, remote: Vec<Entry>) -> Vec<Mismatch> {
+    use Mismatch::*;
+
+    local
+        .into_iter()
+        .filter(Entry::is_file)
+        .merge_join_by(remote.into_iter().filter(Entry::is_file), |a, b| {
+            a.path.cmp(&b.path)
+        })
+        .filter_map(|pair| match pair {
+            Left(l) => Some(Missing(l.path)),
+            Right(r) => Some(Extra(r.path)),
+            Both(l, r) if l.is_same(&r) => None,
+            Both(l, r) => match (l.info, r.info) {
+                (Some(li), Some(ri)) if ri.size < li.size => Some(Truncated(r.path)),
+                _ => Some(Modified(r.path)),
+            },
+        })
+        .collect()
+}
@@ -0,0 +1,194 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+use super::deploy::{self, deploy_site};
+use super::lock::SiteLock;
+use super::preflight;
+use crate::control::Control;
+use crate::params::{DeployArgs, Params, Site, WatchArgs};
+use crate::trees::{self, Entry};
+use anyhow::{bail, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+/// How often to check for cancellation while waiting for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Watch the local files and deploy the site(s) whenever they change.
+pub fn watch(params: &Params, args: &WatchArgs, control: &Control) -> Result<()> {
+    check_args(&args.deploy)?;
+    let sites = args.deploy.sites(params)?;
+    if sites.is_empty() {
+        eprintln!("No sites to watch");
+        return Ok(());
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    let mut watched = Vec::new();
+    for (name, site) in sites {
+        if !preflight::ensure_local_path(&name, &site, args.deploy.create_path)? {
+            continue;
+        }
+        // A site built into an output directory is rebuilt when its sources change, so the
+        // whole local directory is watched, except for the output the build writes.
+        let output = match output_dir(&site) {
+            Some(dir) => Some(Path::new(&site.path).canonicalize()?.join(dir)),
+            None => None,
+        };
+        let mut roots = Vec::new();
+        for (i, source) in site.source_dirs().into_iter().enumerate() {
+            let root = match output {
+                Some(_) if i == 0 => PathBuf::from(&site.path),
+                _ => source.root,
+            };
+            let root = root.canonicalize()?;
+            watcher.watch(&root, RecursiveMode::Recursive)?;
+            tracing::info!("Watching site {} at {:?}", name, root);
+            roots.push(root);
+        }
+        watched.push(WatchedSite {
+            name,
+            site,
+            roots,
+            output,
+            last: None,
+        });
+    }
+
+    // Start with every site in sync with its local files.
+    for watched in &mut watched {
+        watched.sync(params, &args.deploy, control);
+    }
+
+    let debounce = Duration::from_millis(args.debounce);
+    let mut changed = HashSet::new();
+    while !control.is_cancelled() {
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(event) => collect(&mut changed, event),
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        // Wait until the file system has been quiet for the debounce period.
+        loop {
+            match rx.recv_timeout(debounce) {
+                Ok(event) => collect(&mut changed, event),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        for watched in &mut watched {
+            let affected = |path: &PathBuf| {
+                watched.roots.iter().any(|r| path.starts_with(r))
+                    && !watched.output.as_ref().is_some_and(|o| path.starts_with(o))
+            };
+            if changed.iter().any(affected) {
+                watched.sync(params, &args.deploy, control);
+            }
+        }
+        changed.clear();
+    }
+
+    tracing::info!("Stopped watching");
+    Ok(())
+}
+
+/// Fail if any of the deploy options that only make sense for a single deploy is given.
+fn check_args(args: &DeployArgs) -> Result<()> {
+    let unsupported = [
+        ("--tui", args.exec.tui),
+        ("--since", args.plan.since.is_some()),
+        ("--resume", args.resume),
+        ("--archive", args.archive.is_some()),
+        ("--mirror", args.mirror),
+        ("--jobs-sites", args.jobs_sites != 1),
+        ("--metrics-file", args.metrics_file.is_some()),
+    ];
+    match unsupported.iter().find(|(_, given)| *given) {
+        Some((option, _)) => bail!("{} cannot be used with watch", option),
+        None => Ok(()),
+    }
+}
+
+/// The output directory of a site’s build step, if it has one.
+fn output_dir(site: &Site) -> Option<&str> {
+    site.build.as_ref()?.output_dir.as_deref()
+}
+
+/// A site being watched, along with the last local tree that was deployed.
+struct WatchedSite {
+    name: String,
+    site: Site,
+    roots: Vec<PathBuf>,
+    /// Output directory of the build step, whose changes are ignored.
+    output: Option<PathBuf>,
+    last: Option<Vec<Entry>>,
+}
+
+impl WatchedSite {
+    /// Deploy the site if its local tree has changed since the last deploy.
+    ///
+    /// Errors are logged rather than returned, so that watching goes on.
+    fn sync(&mut self, params: &Params, args: &DeployArgs, control: &Control) {
+        if let Err(e) = self.try_sync(params, args, control) {
+            tracing::error!("{}: {}", self.name, e);
+        }
+    }
+
+    /// Run the hooks and the build step of the site, and deploy it if its local tree has changed.
+    ///
+    /// Building the tree honors `.neocitiesignore`, so changes to ignored files never trigger a
+    /// deploy. A site built into an output directory is built before its tree is compared;
+    /// otherwise the tree is compared first, and built again after the `pre_deploy` hook, so that
+    /// files written by the hook do not trigger another deploy.
+    fn try_sync(&mut self, params: &Params, args: &DeployArgs, control: &Control) -> Result<()> {
+        let options = args.plan.tree_options(&self.site);
+        let tree = || trees::merged_tree(&self.site.source_dirs(), true, &options);
+        if self.output.is_none() && self.last.as_ref() == Some(&tree()?) {
+            tracing::debug!("No relevant changes for site {}", self.name);
+            return Ok(());
+        }
+        let _lock = SiteLock::acquire(&self.name)?;
+        let envs = deploy::hook_envs(&self.name, &self.site);
+        deploy::pre_deploy(&self.name, &self.site, &envs, true)?;
+        let local = tree()?;
+        if self.output.is_some() && self.last.as_ref() == Some(&local) {
+            tracing::debug!("No relevant changes for site {}", self.name);
+            return Ok(());
+        }
+        tracing::info!("Deploying site: {}", self.name);
+        let result = deploy_site(params, args, &self.name, &self.site, local.clone(), control);
+        deploy::post_deploy(&self.site, &envs, &result)?;
+        result?;
+        tracing::info!("Site {} is up to date", self.name);
+        self.last = Some(local);
+        Ok(())
+    }
+}
+
+/// Add the paths of a file system event to the set of changed paths.
+fn collect(changed: &mut HashSet<PathBuf>, event: notify::Result<notify::Event>) {
+    match event {
+        Ok(event) if !matches!(event.kind, EventKind::Access(_)) => changed.extend(event.paths),
+        Ok(_) => {}
+        Err(e) => tracing::warn!("{}", e),
+    }
+}
@@ -0,0 +1,146 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Encrypting the configuration file with a passphrase.
+
+use crate::ci;
+use age::scrypt;
+use age::secrecy::{ExposeSecret, SecretString};
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+/// Environment variable holding the passphrase, for when it cannot be asked for.
+pub const PASSPHRASE_VAR: &str = "NEOCITIES_DEPLOY_PASSPHRASE";
+
+/// The passphrase, once it has been asked for, so that a configuration file that is read and then
+/// saved again only asks for it once. It is zeroed when dropped, as are the cached plaintexts.
+static PASSPHRASE: OnceLock<SecretString> = OnceLock::new();
+
+/// Plaintexts of the encrypted contents decrypted or encrypted so far, by their ciphertexts.
+///
+/// Deriving the key from the passphrase with scrypt is deliberately slow, and the configuration is
+/// loaded several times by a single command, so each ciphertext is only decrypted once.
+static PLAINTEXTS: OnceLock<Mutex<HashMap<Vec<u8>, SecretString>>> = OnceLock::new();
+
+fn plaintexts() -> MutexGuard<'static, HashMap<Vec<u8>, SecretString>> {
+    PLAINTEXTS.get_or_init(Mutex::default).lock().unwrap()
+}
+
+/// Whether the contents of a file are encrypted with age, either binary or armored.
+pub fn is_encrypted(contents: &[u8]) -> bool {
+    let start = contents
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(contents.len());
+    let contents = &contents[start..];
+    contents.starts_with(b"age-encryption.org/")
+        || contents.starts_with(b"-----BEGIN AGE ENCRYPTED FILE-----")
+}
+
+/// Decrypt the contents of an encrypted configuration file.
+pub fn decrypt(contents: &[u8]) -> Result<String> {
+    decrypt_with(contents, || passphrase(false))
+}
+
+/// Decrypt contents with a passphrase, which is only asked for when they are not cached.
+fn decrypt_with(
+    contents: &[u8],
+    passphrase: impl FnOnce() -> Result<SecretString>,
+) -> Result<String> {
+    if let Some(plaintext) = plaintexts().get(contents) {
+        return Ok(plaintext.expose_secret().to_owned());
+    }
+    let identity = scrypt::Identity::new(passphrase()?);
+    let plaintext = age::decrypt(&identity, contents)
+        .map_err(|e| anyhow!("Could not decrypt the configuration file: {}", e))?;
+    let plaintext = String::from_utf8(plaintext)?;
+    plaintexts().insert(contents.to_vec(), plaintext.as_str().into());
+    Ok(plaintext)
+}
+
+/// Encrypt the contents of a configuration file, with ASCII armor so that it is still text.
+pub fn encrypt(contents: &str) -> Result<Vec<u8>> {
+    let recipient = scrypt::Recipient::new(passphrase(true)?);
+    let encrypted = age::encrypt_and_armor(&recipient, contents.as_bytes())?.into_bytes();
+    plaintexts().insert(encrypted.clone(), contents.into());
+    Ok(encrypted)
+}
+
+/// Get the passphrase from the environment, or else ask for it (twice, when encrypting).
+fn passphrase(confirm: bool) -> Result<SecretString> {
+    if let Some(passphrase) = PASSPHRASE.get() {
+        return Ok(passphrase.clone());
+    }
+    let passphrase: SecretString = match env::var(PASSPHRASE_VAR) {
+        Ok(passphrase) => passphrase,
+        Err(_) if !ci::interactive() => {
+            bail!(
+                "The configuration file is encrypted; set {} to its passphrase",
+                PASSPHRASE_VAR
+            )
+        }
+        Err(_) => {
+            let prompt = inquire::Password::new("Configuration passphrase:");
+            match confirm {
+                true => prompt.prompt()?,
+                false => prompt.without_confirmation().prompt()?,
+            }
+        }
+    }
+    .into();
+    if passphrase.expose_secret().is_empty() {
+        bail!("The passphrase must not be empty");
+    }
+    Ok(PASSPHRASE.get_or_init(|| passphrase).clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_encrypted() {
+        assert!(is_encrypted(b"age-encryption.org/v1\n-> scrypt"));
+        assert!(is_encrypted(b"\n-----BEGIN AGE ENCRYPTED FILE-----\n"));
+        assert!(!is_encrypted(b"[site.\"lorem.com\"]\n"));
+        assert!(!is_encrypted(b""));
+    }
+
+    #[test]
+    fn test_decrypt() {
+        let mut recipient = scrypt::Recipient::new(SecretString::from("secret".to_owned()));
+        recipient.set_work_factor(4);
+        let contents = age::encrypt_and_armor(&recipient, b"[site]\n").unwrap();
+        assert!(is_encrypted(contents.as_bytes()));
+        let secret = || Ok(SecretString::from("secret"));
+        assert_eq!(
+            decrypt_with(contents.as_bytes(), secret).unwrap(),
+            "[site]\n"
+        );
+        // Decrypting the same contents again takes the plaintext from the cache, without asking
+        // for the passphrase.
+        assert!(plaintexts().contains_key(contents.as_bytes()));
+        let asked = || bail!("The passphrase was asked for");
+        assert_eq!(
+            decrypt_with(contents.as_bytes(), asked).unwrap(),
+            "[site]\n"
+        );
+    }
+}
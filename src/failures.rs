@@ -0,0 +1,65 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Actions that failed without stopping the command, reported together when it finishes.
+
+use crate::ci;
+use crate::plan::Action;
+use std::process::ExitCode;
+use std::sync::Mutex;
+
+/// Exit code of a command that finished with some of its actions failed.
+pub const PARTIAL_SUCCESS: u8 = 3;
+
+/// Failed actions of each site, with their errors, in the order they were recorded.
+static FAILURES: Mutex<Vec<(String, String, String)>> = Mutex::new(Vec::new());
+
+/// Record the actions of a site that failed.
+pub fn record(site: &str, failures: &[(Action, String)]) {
+    let mut recorded = FAILURES.lock().unwrap();
+    for (action, error) in failures {
+        recorded.push((site.to_owned(), action.to_string(), error.clone()));
+    }
+}
+
+/// Report all the failed actions, if any, and give the exit code of the command: partial success
+/// if any action failed.
+pub fn report() -> ExitCode {
+    let failures = FAILURES.lock().unwrap();
+    if failures.is_empty() {
+        return ExitCode::SUCCESS;
+    }
+    let message = format!("Incomplete, {} action(s) failed:", failures.len());
+    tracing::error!("{}", message);
+    for (site, action, error) in failures.iter() {
+        tracing::error!("  {}: {}: {}", site, action, error);
+    }
+    if ci::enabled() {
+        let lines = failures
+            .iter()
+            .map(|(site, action, error)| format!("{}: {}: {}", site, action, error));
+        ci::annotate(
+            [message]
+                .into_iter()
+                .chain(lines)
+                .collect::<Vec<_>>()
+                .join("\n"),
+        );
+    }
+    ExitCode::from(PARTIAL_SUCCESS)
+}
@@ -0,0 +1,81 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Asking git which files changed.
+
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+use std::path::Path;
+use std::process::Command;
+
+/// Paths that changed in a directory since a git ref, relative to that directory.
+#[derive(Debug, Default)]
+pub struct Changes {
+    /// Paths that were added or modified, including untracked files.
+    pub changed: HashSet<String>,
+    /// Paths that were deleted.
+    pub deleted: Vec<String>,
+}
+
+/// Find the files that changed in `dir` between `git_ref` and the working tree.
+pub fn changes_since(dir: &Path, git_ref: &str) -> Result<Changes> {
+    let mut changes = Changes::default();
+    let diff = git(
+        dir,
+        &["diff", "--name-status", "--no-renames", "--relative", "-z"],
+        &[git_ref, "--"],
+    )?;
+    let mut fields = diff.split('\0').filter(|f| !f.is_empty());
+    while let (Some(status), Some(path)) = (fields.next(), fields.next()) {
+        if status.starts_with('D') {
+            changes.deleted.push(path.to_owned());
+        } else {
+            changes.changed.insert(path.to_owned());
+        }
+    }
+    let untracked = git(
+        dir,
+        &["ls-files", "--others", "--exclude-standard", "-z"],
+        &[],
+    )?;
+    changes.changed.extend(
+        untracked
+            .split('\0')
+            .filter(|f| !f.is_empty())
+            .map(str::to_owned),
+    );
+    Ok(changes)
+}
+
+/// Run a git command in a directory and return its output.
+fn git(dir: &Path, args: &[&str], operands: &[&str]) -> Result<String> {
+    tracing::debug!("Running git {:?} {:?} in {:?}", args, operands, dir);
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .args(operands)
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
@@ -0,0 +1,71 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Running user-configured shell commands, such as deploy hooks.
+
+use anyhow::{bail, Result};
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Run a command through the system shell, with additional environment variables, optionally in
+/// another working directory.
+///
+/// The command’s standard output is redirected to the standard error, so that it does not mix
+/// with machine-readable output. Fails if the command exits with a non-zero status.
+pub fn run(command: &str, dir: Option<&Path>, envs: &[(&str, String)]) -> Result<()> {
+    tracing::info!("Running: {}", command);
+    let mut cmd = shell(command);
+    if let Some(dir) = dir {
+        cmd.current_dir(dir);
+    }
+    let status = cmd
+        .envs(envs.iter().map(|(k, v)| (k, v)))
+        .stdout(io::stderr())
+        .status()?;
+    if !status.success() {
+        bail!("Command `{}` failed with {}", command, status);
+    }
+    Ok(())
+}
+
+/// Run a command through the system shell and return its standard output.
+///
+/// The standard input and error are left attached to the terminal, so that the command can ask
+/// for a passphrase. Fails if the command exits with a non-zero status.
+pub fn output(command: &str) -> Result<String> {
+    tracing::debug!("Running: {}", command);
+    let output = shell(command).stderr(Stdio::inherit()).output()?;
+    if !output.status.success() {
+        bail!("Command `{}` failed with {}", command, output.status);
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Build a command that runs a command line through the system shell.
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
@@ -0,0 +1,198 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+use crate::params::Site;
+use crate::plan::Action;
+use crate::trees::{self, Entry};
+use anyhow::{ensure, Result};
+use bytesize::ByteSize;
+use std::fs;
+
+/// Size from which images are recompressed, unless the site sets another one.
+const DEFAULT_MIN_SIZE: ByteSize = ByteSize::kb(100);
+/// Quality of the recompressed JPEG images, unless the site sets another one.
+const DEFAULT_JPEG_QUALITY: u8 = 85;
+
+/// Image formats that can be recompressed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Png,
+    Jpeg,
+    WebP,
+}
+
+impl Format {
+    /// Guess the format of an image from the extension of its path.
+    fn of(path: &str) -> Option<Self> {
+        let (_, extension) = path.rsplit_once('.')?;
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "webp" => Some(Self::WebP),
+            _ => None,
+        }
+    }
+}
+
+/// Recompress the large images of a local tree, if the site asks for it with `optimize_images`.
+///
+/// The recompressed image replaces the original only when it is smaller. PNG and WebP images are
+/// recompressed losslessly, and JPEG images at `jpeg_quality`. Animated images are left alone,
+/// and so are the images of archives, which are already in memory. An image that can’t be
+/// recompressed is uploaded as it is, with a warning.
+pub fn optimize(local: &mut [Entry], site: &Site) -> Result<()> {
+    let Some(options) = &site.optimize_images else {
+        return Ok(());
+    };
+    if !cfg!(feature = "optimize-images") {
+        tracing::warn!("Not optimizing images, this build lacks the `optimize-images` feature");
+        return Ok(());
+    }
+    let min_size = options.min_size.unwrap_or(DEFAULT_MIN_SIZE).as_u64();
+    let quality = options.jpeg_quality.unwrap_or(DEFAULT_JPEG_QUALITY);
+    ensure!(
+        (1..=100).contains(&quality),
+        "The JPEG quality must be between 1 and 100, not {}",
+        quality
+    );
+    for entry in local.iter_mut() {
+        let (Some(info), Some(local_path)) = (&mut entry.info, &entry.local_path) else {
+            continue;
+        };
+        let Some(format) = Format::of(&entry.path).filter(|_| info.size >= min_size) else {
+            continue;
+        };
+        let result = fs::read(local_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|original| Ok((recompress(format, &original, quality)?, original)));
+        match result {
+            Ok((Some(smaller), original)) if smaller.len() < original.len() => {
+                tracing::debug!(
+                    "Optimized {}: {} -> {}",
+                    entry.path,
+                    ByteSize::b(original.len() as u64),
+                    ByteSize::b(smaller.len() as u64)
+                );
+                info.size = smaller.len() as u64;
+                info.sha1_sum = trees::sha1_bytes(&smaller);
+                entry.content = Some(smaller.into());
+            }
+            Ok(_) => tracing::debug!("Not optimizing {}, it wouldn’t get smaller", entry.path),
+            Err(e) => tracing::warn!("Failed to optimize {}: {:#}", entry.path, e),
+        }
+    }
+    Ok(())
+}
+
+/// Count the bytes saved by the optimized images among the uploads of some actions.
+pub fn saved<'a>(actions: impl IntoIterator<Item = &'a Action>) -> u64 {
+    actions
+        .into_iter()
+        .filter_map(|action| match action {
+            Action::Upload(entry) if entry.content.is_some() => {
+                let original = fs::metadata(entry.local_path.as_ref()?).ok()?.len();
+                Some(original.saturating_sub(entry.info.as_ref()?.size))
+            }
+            _ => None,
+        })
+        .sum()
+}
+
+/// Decode an image and encode it again, returning `None` if it is animated.
+#[cfg(feature = "optimize-images")]
+fn recompress(format: Format, original: &[u8], quality: u8) -> Result<Option<Vec<u8>>> {
+    use image::codecs::jpeg::{JpegDecoder, JpegEncoder};
+    use image::codecs::png::{CompressionType, FilterType, PngDecoder, PngEncoder};
+    use image::codecs::webp::{WebPDecoder, WebPEncoder};
+    use image::{DynamicImage, ImageDecoder};
+    use std::io::Cursor;
+
+    // The orientation is applied to the pixels, since the metadata holding it is not kept.
+    fn decode(mut decoder: impl ImageDecoder) -> Result<DynamicImage> {
+        let orientation = decoder.orientation()?;
+        let mut image = DynamicImage::from_decoder(decoder)?;
+        image.apply_orientation(orientation);
+        Ok(image)
+    }
+
+    let reader = Cursor::new(original);
+    let mut output = Vec::new();
+    match format {
+        Format::Png => {
+            let decoder = PngDecoder::new(reader)?;
+            if decoder.is_apng()? {
+                return Ok(None);
+            }
+            let encoder = PngEncoder::new_with_quality(
+                &mut output,
+                CompressionType::Best,
+                FilterType::Adaptive,
+            );
+            decode(decoder)?.write_with_encoder(encoder)?;
+        }
+        Format::Jpeg => {
+            let encoder = JpegEncoder::new_with_quality(&mut output, quality);
+            decode(JpegDecoder::new(reader)?)?.write_with_encoder(encoder)?;
+        }
+        Format::WebP => {
+            let decoder = WebPDecoder::new(reader)?;
+            if decoder.has_animation() {
+                return Ok(None);
+            }
+            decode(decoder)?.write_with_encoder(WebPEncoder::new_lossless(&mut output))?;
+        }
+    }
+    Ok(Some(output))
+}
+
+#[cfg(not(feature = "optimize-images"))]
+fn recompress(_: Format, _: &[u8], _: u8) -> Result<Option<Vec<u8>>> {
+    anyhow::bail!("This build lacks the `optimize-images` feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_of() {
+        assert_eq!(Format::of("a/b.png"), Some(Format::Png));
+        assert_eq!(Format::of("photo.JPG"), Some(Format::Jpeg));
+        assert_eq!(Format::of("photo.jpeg"), Some(Format::Jpeg));
+        assert_eq!(Format::of("image.webp"), Some(Format::WebP));
+        assert_eq!(Format::of("index.html"), None);
+        assert_eq!(Format::of("png"), None);
+    }
+
+    #[cfg(feature = "optimize-images")]
+    #[test]
+    fn test_recompress() {
+        use image::{ImageFormat, RgbImage};
+        use std::io::Cursor;
+
+        let image = RgbImage::from_fn(64, 64, |x, _| image::Rgb([x as u8 * 4, 0, 0]));
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let smaller = recompress(Format::Png, &png, 85).unwrap().unwrap();
+        let decoded = image::load_from_memory(&smaller).unwrap().to_rgb8();
+        assert_eq!(decoded, image);
+        assert!(recompress(Format::Jpeg, b"not an image", 85).is_err());
+    }
+}
@@ -0,0 +1,401 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Logging setup.
+//!
+//! Messages are `tracing` events, and the deploy of each site and each of its actions are spans,
+//! whose fields (`site`, and the `action`, `path` and `bytes` of the action) are added to the
+//! messages logged within them. Messages from libraries that use `log` are forwarded to the same
+//! subscriber.
+
+use crate::ci;
+use crate::params::LogFormat;
+use crate::style;
+use anyhow::{Context as _, Result};
+use colored::{ColoredString, Colorize};
+use serde_json::{json, Map, Value};
+use std::fmt::{self, Write as _};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use std::{env, thread};
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+/// Console messages held back while the terminal UI is shown.
+static HELD: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+/// Install the subscriber, writing the messages allowed by `RUST_LOG` and the verbosity to the
+/// console as text (with colors or not) or JSON lines, and, with a log file, the messages of this
+/// program down to the debug level to the file, whatever the verbosity. The file is overwritten.
+///
+/// Messages logged from a named thread (other than the main one) are prefixed with its name.
+/// Sites deployed concurrently each run in a thread named after the site, so that their
+/// interleaved messages can be told apart.
+///
+/// Warnings are shown in yellow, and messages about the actions of a deploy (those within the
+/// span of an action) in the color of its kind. In the non-interactive mode, errors are also
+/// reported as GitHub Actions annotations.
+pub fn init(
+    format: LogFormat,
+    colors: bool,
+    verbosity: LevelFilter,
+    log_file: Option<&Path>,
+) -> Result<()> {
+    let targets = env::var("RUST_LOG")
+        .ok()
+        .and_then(|filters| filters.parse().ok())
+        .unwrap_or_else(|| Targets::new().with_target("neocities_deploy", LevelFilter::TRACE));
+    let console = Console {
+        format,
+        colors,
+        verbosity,
+        targets,
+        width: AtomicUsize::new(0),
+    };
+    let file = match log_file {
+        Some(path) => Some(LogFile(Mutex::new(
+            File::create(path)
+                .with_context(|| format!("Could not create the log file {:?}", path))?,
+        ))),
+        None => None,
+    };
+    let max_level = match file {
+        Some(_) => verbosity.max(LevelFilter::DEBUG),
+        None => verbosity,
+    };
+    tracing_subscriber::registry()
+        .with(max_level)
+        .with(Spans)
+        .with(console)
+        .with(file)
+        .try_init()?;
+    Ok(())
+}
+
+/// Hold back the console messages until the returned guard is dropped, when they are written.
+pub fn hold() -> Held {
+    HELD.lock().unwrap().get_or_insert_with(Vec::new);
+    Held
+}
+
+/// Writes the messages held back by [`hold`] when dropped.
+pub struct Held;
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        let held = HELD.lock().unwrap().take().unwrap_or_default();
+        let mut stderr = io::stderr().lock();
+        for line in held {
+            let _ = writeln!(stderr, "{}", line);
+        }
+    }
+}
+
+/// Write a line to the console, unless it is held back.
+fn emit(line: String) {
+    match HELD.lock().unwrap().as_mut() {
+        Some(held) => held.push(line),
+        None => {
+            let _ = writeln!(io::stderr().lock(), "{}", line);
+        }
+    }
+}
+
+/// Fields of a span, and when it was created.
+struct SpanData {
+    fields: Map<String, Value>,
+    started: Instant,
+}
+
+/// Keeps the fields of the spans, for the layers that write the messages logged within them.
+struct Spans;
+
+impl<S> Layer<S> for Spans
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanData {
+                fields: fields.fields,
+                started: Instant::now(),
+            });
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = Fields::default();
+        values.record(&mut fields);
+        let mut extensions = span.extensions_mut();
+        if let Some(data) = extensions.get_mut::<SpanData>() {
+            data.fields.extend(fields.fields);
+        }
+    }
+}
+
+/// Writes messages to the standard error.
+struct Console {
+    format: LogFormat,
+    colors: bool,
+    verbosity: LevelFilter,
+    targets: Targets,
+    /// Width of the longest target so far, to which targets are padded.
+    width: AtomicUsize,
+}
+
+impl Console {
+    /// Write a message as text, prefixed with its level and target.
+    fn text(&self, metadata: &Metadata, fields: &Map<String, Value>, message: String) {
+        let message = match fields.get("action").and_then(Value::as_str) {
+            Some(kind) => style::action(kind, message).to_string(),
+            None if *metadata.level() == Level::WARN => style::warning(message).to_string(),
+            None => message,
+        };
+        let message = match thread::current().name() {
+            Some(name) if name != "main" => format!("[{}] {}", name, message),
+            _ => message,
+        };
+        let target = metadata.target();
+        let width = self
+            .width
+            .fetch_max(target.len(), Ordering::Relaxed)
+            .max(target.len());
+        let target = format!("{:<width$}", target, width = width);
+        let (level, target) = match self.colors {
+            true => (level(metadata.level()), target.bold()),
+            false => (format!("{:<5}", metadata.level()).normal(), target.normal()),
+        };
+        emit(format!(" {} {} > {}", level, target, message));
+    }
+
+    /// Write a message as a line of JSON, with its timestamp, level, target, the site it is about
+    /// (if any) and its other fields (such as the `action`, `path` and `bytes` of the actions of
+    /// a deploy).
+    fn json(&self, metadata: &Metadata, mut fields: Map<String, Value>, message: String) {
+        let mut line = json!({
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "level": metadata.level().as_str(),
+            "target": metadata.target(),
+            "site": fields.remove("site").unwrap_or(Value::Null),
+            "message": message,
+        });
+        line.as_object_mut().unwrap().extend(fields);
+        emit(line.to_string());
+    }
+}
+
+impl<S> Layer<S> for Console
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if *metadata.level() > self.verbosity
+            || !self
+                .targets
+                .would_enable(metadata.target(), metadata.level())
+        {
+            return;
+        }
+        let mut fields = scope_fields(event, &ctx);
+        let mut visitor = Fields::default();
+        event.record(&mut visitor);
+        fields.extend(visitor.fields);
+        let message = visitor.message.unwrap_or_default();
+        if ci::enabled() && *metadata.level() == Level::ERROR {
+            ci::annotate(&message);
+        }
+        match self.format {
+            LogFormat::Text => self.text(metadata, &fields, message),
+            LogFormat::Json => self.json(metadata, fields, message),
+        }
+    }
+}
+
+/// Writes the messages of this program, down to the debug level, to a file, along with the spans
+/// they were logged in and how long each span took.
+struct LogFile(Mutex<File>);
+
+impl LogFile {
+    /// Whether a message or span goes to the file.
+    fn enabled(metadata: &Metadata) -> bool {
+        *metadata.level() <= Level::DEBUG && metadata.target().starts_with("neocities_")
+    }
+
+    /// Write a line to the file.
+    fn write(&self, level: &Level, target: &str, spans: &str, message: impl fmt::Display) {
+        let _ = writeln!(
+            self.0.lock().unwrap(),
+            "{} {:<5} {} > {}{}",
+            chrono::Utc::now().to_rfc3339(),
+            level,
+            target,
+            spans,
+            message
+        );
+    }
+}
+
+impl<S> Layer<S> for LogFile
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if !Self::enabled(metadata) {
+            return;
+        }
+        let mut spans = String::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                write_span(&mut spans, span.name(), span.extensions().get::<SpanData>());
+            }
+        }
+        let mut visitor = Fields::default();
+        event.record(&mut visitor);
+        let mut message = visitor.message.unwrap_or_default();
+        write_fields(&mut message, &visitor.fields);
+        self.write(metadata.level(), metadata.target(), &spans, message);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        if !Self::enabled(span.metadata()) {
+            return;
+        }
+        let mut spans = String::new();
+        for span in span.scope().from_root() {
+            write_span(&mut spans, span.name(), span.extensions().get::<SpanData>());
+        }
+        let extensions = span.extensions();
+        if let Some(data) = extensions.get::<SpanData>() {
+            let message = format!("closed after {:?}", data.started.elapsed());
+            self.write(&Level::DEBUG, span.metadata().target(), &spans, message);
+        }
+    }
+}
+
+/// Collect the fields of the spans a message was logged in, from the outermost to the innermost.
+fn scope_fields<S>(event: &Event<'_>, ctx: &Context<'_, S>) -> Map<String, Value>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let mut fields = Map::new();
+    if let Some(scope) = ctx.event_scope(event) {
+        for span in scope.from_root() {
+            if let Some(data) = span.extensions().get::<SpanData>() {
+                fields.extend(data.fields.clone());
+            }
+        }
+    }
+    fields
+}
+
+/// Write a span as `name{key=value ...}: `.
+fn write_span(out: &mut String, name: &str, data: Option<&SpanData>) {
+    out.push_str(name);
+    if let Some(data) = data.filter(|data| !data.fields.is_empty()) {
+        let mut fields = String::new();
+        write_fields(&mut fields, &data.fields);
+        out.push('{');
+        out.push_str(fields.trim_start());
+        out.push('}');
+    }
+    out.push_str(": ");
+}
+
+/// Write fields as ` key=value`.
+fn write_fields(out: &mut String, fields: &Map<String, Value>) {
+    for (key, value) in fields {
+        let _ = match value {
+            Value::String(value) => write!(out, " {}={}", key, value),
+            value => write!(out, " {}={}", key, value),
+        };
+    }
+}
+
+/// Style a level as `pretty_env_logger` did.
+fn level(level: &Level) -> ColoredString {
+    let text = format!("{:<5}", level);
+    match *level {
+        Level::TRACE => text.magenta(),
+        Level::DEBUG => text.blue(),
+        Level::INFO => text.green(),
+        Level::WARN => text.yellow(),
+        Level::ERROR => text.red(),
+    }
+}
+
+/// Collects the message and the other fields of an event or span, as JSON.
+#[derive(Default)]
+struct Fields {
+    message: Option<String>,
+    fields: Map<String, Value>,
+}
+
+impl Fields {
+    fn insert(&mut self, field: &Field, value: Value) {
+        // The fields added to the messages forwarded from `log` are left out.
+        if !field.name().starts_with("log.") {
+            self.fields.insert(field.name().to_owned(), value);
+        }
+    }
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.message = Some(format!("{:?}", value)),
+            _ => self.insert(field, json!(format!("{:?}", value))),
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, json!(value));
+    }
+}
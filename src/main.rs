@@ -16,30 +16,88 @@
 ////////       You should have received a copy of the GNU General Public License           ////////
 ////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
 
+mod archive;
+mod ci;
 mod commands;
+mod encryption;
+mod failures;
+mod git;
+mod hooks;
+mod images;
+mod logging;
 mod params;
-mod trees;
+mod signals;
+mod style;
+mod tls;
+mod tui;
 
 use anyhow::Result;
-use clap::Parser;
+use neocities_deploy_core::{control, executor, plan, trees};
 use params::{Command, Params};
 use std::env;
+use std::process::ExitCode;
 
-fn main() -> Result<()> {
+fn main() -> Result<ExitCode> {
     if env::var("RUST_LOG").is_err() {
         env::set_var("RUST_LOG", "neocities_deploy");
     }
-    pretty_env_logger::try_init()?;
-
-    let params = Params::parse();
-    log::set_max_level(params.verbosity());
+    if let Some((params, names)) = Params::parse_complete() {
+        commands::complete(&params, names)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    let params = Params::parse_with_aliases();
+    ci::init(params.ci);
+    logging::init(
+        params.log_format,
+        style::init(params.color),
+        params.verbosity(),
+        params.log_file().as_deref(),
+    )?;
 
-    match params.command {
-        Command::Config => commands::config(&params),
-        Command::Key => commands::key(&params),
-        Command::List => commands::list(&params),
-        Command::Deploy => commands::deploy(&params),
-    }?;
+    let result = run(&params);
+    if let Err(e) = &result {
+        if params.ci {
+            ci::annotate(format!("{:#}", e));
+        }
+    } else if params::used_credentials() {
+        if let Err(e) = commands::offer_api_keys(&params) {
+            tracing::warn!("Failed to replace the password(s) with API key(s): {:#}", e);
+        }
+    }
+    result?;
+    Ok(failures::report())
+}
 
-    Ok(())
+/// Run the subcommand, making sure there is a configuration file first if it needs one.
+fn run(params: &Params) -> Result<()> {
+    if params.command.needs_config() {
+        commands::ensure_config(params)?;
+    }
+    let control = control::Control::new();
+    if params.command.deploys() {
+        signals::handle(&control)?;
+    }
+    match &params.command {
+        Command::Config(args) => commands::config(params, args),
+        Command::Init(args) => commands::init(params, args),
+        Command::Key(args) => commands::key(params, args),
+        Command::Logout(args) => commands::logout(params, args),
+        Command::Info(args) => commands::info(params, args),
+        Command::List(args) => commands::list(params, args),
+        Command::Report(args) => commands::report(params, args),
+        Command::Deploy(args) => commands::deploy(params, args, &control),
+        Command::Plan(args) => commands::plan(params, args),
+        Command::Apply(args) => commands::apply(params, args, &control),
+        Command::Promote(args) => commands::promote(params, args, &control),
+        Command::Open(args) => commands::open(params, args),
+        Command::Verify(_) => commands::verify(params),
+        Command::CheckLinks(_) => commands::check_links(params),
+        Command::Diff(args) => commands::diff(params, args),
+        Command::Snapshot(args) => commands::snapshot(params, args),
+        Command::History(args) => commands::history(params, args),
+        Command::Rollback(args) => commands::rollback(params, args, &control),
+        Command::Watch(args) => commands::watch(params, args, &control),
+        Command::Doctor(_) => commands::doctor(params),
+        Command::Completions(args) => commands::completions(args),
+    }
 }
@@ -0,0 +1,67 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Controlling the running deploys from the terminal: Ctrl-C cancels them, and Ctrl-Z suspends
+//! them.
+
+use crate::control::Control;
+use anyhow::Result;
+
+/// Cancel the deploys when the user presses Ctrl-C; a second Ctrl-C exits immediately. On Unix,
+/// Ctrl-Z (`SIGTSTP`) pauses them and then suspends the process as usual, and they resume when
+/// the process is continued (`SIGCONT`, as sent by `fg`).
+///
+/// This can only be done once per process, so `main` does it for the subcommands that deploy.
+pub fn handle(control: &Control) -> Result<()> {
+    let cancel = control.clone();
+    ctrlc::set_handler(move || {
+        if cancel.is_cancelled() {
+            std::process::exit(130);
+        }
+        tracing::warn!("Cancelling after the current request; press Ctrl-C again to abort");
+        cancel.cancel();
+    })?;
+    #[cfg(unix)]
+    suspend_on_sigtstp(control)?;
+    Ok(())
+}
+
+/// Pause on `SIGTSTP` before suspending the process, and resume on `SIGCONT`.
+#[cfg(unix)]
+fn suspend_on_sigtstp(control: &Control) -> Result<()> {
+    use signal_hook::consts::{SIGCONT, SIGTSTP};
+    use signal_hook::low_level::emulate_default_handler;
+    let mut signals = signal_hook::iterator::Signals::new([SIGTSTP, SIGCONT])?;
+    let control = control.clone();
+    std::thread::spawn(move || {
+        for signal in signals.forever() {
+            if signal == SIGTSTP {
+                control.pause();
+                tracing::warn!("Suspending; no new request is started until resumed");
+                // Stop the process, as if the signal were not handled.
+                if let Err(e) = emulate_default_handler(SIGTSTP) {
+                    tracing::error!("Failed to suspend: {}", e);
+                }
+            } else if control.is_paused() {
+                control.resume();
+                tracing::info!("Resuming");
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,63 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Colors in the output.
+
+use crate::params::ColorChoice;
+use colored::{ColoredString, Colorize};
+use std::env;
+use std::fmt::Display;
+use std::io::{self, IsTerminal};
+
+/// Decide whether to use colors, and set up [`colored`] accordingly. With `auto`, colors are used
+/// when both the standard output and the standard error are terminals, unless `NO_COLOR` is set.
+pub fn init(choice: ColorChoice) -> bool {
+    let enabled = match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                && io::stdout().is_terminal()
+                && io::stderr().is_terminal()
+        }
+    };
+    colored::control::set_override(enabled);
+    enabled
+}
+
+/// Style text about an action of a deploy, given the action’s kind (see
+/// [`Action::kind`](neocities_deploy_core::plan::Action::kind)): green for uploads, red for
+/// deletions.
+pub fn action(kind: &str, text: impl Display) -> ColoredString {
+    let text = text.to_string();
+    match kind {
+        "upload" => text.green(),
+        "delete" => text.red(),
+        _ => text.normal(),
+    }
+}
+
+/// Style a warning.
+pub fn warning(text: impl Display) -> ColoredString {
+    text.to_string().yellow()
+}
+
+/// Style the name of a directory.
+pub fn directory(text: impl Display) -> ColoredString {
+    text.to_string().blue().bold()
+}
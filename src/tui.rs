@@ -0,0 +1,458 @@
+////////       This file is part of the source code for neocities-deploy, a command-       ////////
+////////       line tool for deploying your Neocities site.                                ////////
+////////                                                                                   ////////
+////////                           Copyright © 2024  André Kugland                         ////////
+////////                                                                                   ////////
+////////       This program is free software: you can redistribute it and/or modify        ////////
+////////       it under the terms of the GNU General Public License as published by        ////////
+////////       the Free Software Foundation, either version 3 of the License, or           ////////
+////////       (at your option) any later version.                                         ////////
+////////                                                                                   ////////
+////////       This program is distributed in the hope that it will be useful,             ////////
+////////       but WITHOUT ANY WARRANTY; without even the implied warranty of              ////////
+////////       MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the                ////////
+////////       GNU General Public License for more details.                                ////////
+////////                                                                                   ////////
+////////       You should have received a copy of the GNU General Public License           ////////
+////////       along with this program. If not, see https://www.gnu.org/licenses/.         ////////
+
+//! Terminal UI for reviewing the actions of a deploy and following their progress.
+//!
+//! The actions are shown as a tree of the directories they touch. Before the deploy, actions (or
+//! whole directories) can be toggled on and off; during the deploy, each action shows whether it
+//! is running, done or failed, with its error. Console log messages are held back while the UI is
+//! shown, and written when it closes.
+
+use crate::ci;
+use crate::control::Control;
+use crate::executor::{Event, Progress, Summary};
+use crate::logging;
+use crate::plan::Action;
+use anyhow::Result;
+use bytesize::ByteSize;
+use ratatui::crossterm::event::{self, Event as TermEvent, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How often the progress is redrawn.
+const TICK: Duration = Duration::from_millis(100);
+
+/// Show the actions of a deploy of a site, and let the user choose which ones to run. Returns the
+/// chosen actions, in their original order, or `None` if the user cancelled the deploy.
+pub fn review(site: &str, actions: Vec<Action>) -> Result<Option<Vec<Action>>> {
+    ci::ensure_interactive("the actions to run (--tui needs a terminal)")?;
+    let _held = logging::hold();
+    let mut view = View::new(site, actions, true);
+    let mut terminal = ratatui::try_init()?;
+    let result = view.review(&mut terminal);
+    ratatui::try_restore()?;
+    Ok(result?.then(|| view.chosen()))
+}
+
+/// Shows the progress of a deploy while it runs, until it is finished.
+pub struct Monitor {
+    view: Arc<Mutex<View>>,
+    ui: JoinHandle<Result<()>>,
+    _held: logging::Held,
+}
+
+impl Monitor {
+    /// Start showing the progress of the actions of a deploy of a site. Pressing `p` pauses or
+    /// resumes the deploy with `control`, and `q` cancels it.
+    pub fn start(site: &str, actions: &[Action], control: Control) -> Result<Self> {
+        let held = logging::hold();
+        let view = Arc::new(Mutex::new(View::new(site, actions.to_vec(), false)));
+        let terminal = ratatui::try_init()?;
+        let ui = {
+            let view = view.clone();
+            thread::spawn(move || follow(terminal, &view, &control))
+        };
+        Ok(Self {
+            view,
+            ui,
+            _held: held,
+        })
+    }
+
+    /// Show the result of the deploy, and wait for the user to close the UI.
+    pub fn finish(self, result: &Result<Summary>) -> Result<()> {
+        self.view.lock().unwrap().result = Some(match result {
+            Ok(summary) => Ok(format!(
+                "Done: {} uploaded, {} deleted, {} failed",
+                summary.uploaded, summary.deleted, summary.failed
+            )),
+            Err(e) => Err(format!("{:#}", e)),
+        });
+        let shown = self.ui.join().unwrap_or(Ok(()));
+        ratatui::try_restore()?;
+        shown
+    }
+}
+
+impl Progress for Monitor {
+    fn event(&self, action: &Action, event: &Event) {
+        let mut view = self.view.lock().unwrap();
+        let Some(index) = view.actions.iter().position(|a| a == action) else {
+            return;
+        };
+        view.status[index] = match event {
+            Event::Planned | Event::Skipped => Status::Pending,
+            Event::Started => Status::Running,
+            Event::Completed { duration } => Status::Done(*duration),
+            Event::Failed { error, .. } => Status::Failed(error.clone()),
+        };
+    }
+}
+
+/// Redraw the progress until the deploy is finished and the user presses a key.
+fn follow(mut terminal: DefaultTerminal, view: &Mutex<View>, control: &Control) -> Result<()> {
+    loop {
+        terminal.draw(|frame| {
+            let mut view = view.lock().unwrap();
+            view.paused = control.is_paused();
+            view.draw(frame)
+        })?;
+        if !event::poll(TICK)? {
+            continue;
+        }
+        let TermEvent::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let mut view = view.lock().unwrap();
+        if view.result.is_some() {
+            return Ok(());
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => control.cancel(),
+            KeyCode::Char('p') => control.toggle_pause(),
+            _ => view.navigate(key),
+        }
+    }
+}
+
+/// State of an action.
+#[derive(Clone, Debug, PartialEq)]
+enum Status {
+    Pending,
+    Running,
+    Done(Duration),
+    Failed(String),
+}
+
+/// Row of the tree of actions.
+#[derive(Debug, PartialEq)]
+enum Row {
+    /// A directory, holding the actions below it.
+    Dir { path: String, depth: usize },
+    /// An action, by its index.
+    Action { index: usize, depth: usize },
+}
+
+/// What is shown by the UI.
+struct View {
+    site: String,
+    actions: Vec<Action>,
+    /// Whether the actions can be toggled, before the deploy.
+    reviewing: bool,
+    chosen: Vec<bool>,
+    status: Vec<Status>,
+    rows: Vec<Row>,
+    list: ListState,
+    /// Whether the deploy is paused.
+    paused: bool,
+    /// Outcome of the deploy, once it is finished.
+    result: Option<Result<String, String>>,
+}
+
+impl View {
+    fn new(site: &str, actions: Vec<Action>, reviewing: bool) -> Self {
+        Self {
+            site: site.to_owned(),
+            chosen: vec![true; actions.len()],
+            status: vec![Status::Pending; actions.len()],
+            rows: rows(&actions),
+            actions,
+            reviewing,
+            list: ListState::default().with_selected(Some(0)),
+            paused: false,
+            result: None,
+        }
+    }
+
+    /// Let the user toggle actions until they start the deploy (`true`) or cancel it.
+    fn review(&mut self, terminal: &mut DefaultTerminal) -> Result<bool> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let TermEvent::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Enter => return Ok(true),
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+                KeyCode::Char(' ') => self.toggle(),
+                KeyCode::Char('a') => {
+                    let all = self.chosen.iter().all(|chosen| *chosen);
+                    self.chosen.fill(!all);
+                }
+                _ => self.navigate(key),
+            }
+        }
+    }
+
+    /// Move the selection.
+    fn navigate(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.list.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => self.list.select_next(),
+            KeyCode::PageUp => self.list.scroll_up_by(10),
+            KeyCode::PageDown => self.list.scroll_down_by(10),
+            KeyCode::Home => self.list.select_first(),
+            KeyCode::End => self.list.select_last(),
+            _ => {}
+        }
+    }
+
+    /// Toggle the selected action, or all the actions below the selected directory.
+    fn toggle(&mut self) {
+        let Some(row) = self.list.selected().and_then(|i| self.rows.get(i)) else {
+            return;
+        };
+        match row {
+            Row::Action { index, .. } => self.chosen[*index] = !self.chosen[*index],
+            Row::Dir { path, .. } => {
+                let below = self.below(path);
+                let all = below.iter().all(|&index| self.chosen[index]);
+                for index in below {
+                    self.chosen[index] = !all;
+                }
+            }
+        }
+    }
+
+    /// Indices of the actions below a directory.
+    fn below(&self, dir: &str) -> Vec<usize> {
+        let prefix = format!("{}/", dir);
+        (0..self.actions.len())
+            .filter(|&index| self.actions[index].entry().path.starts_with(&prefix))
+            .collect()
+    }
+
+    /// The chosen actions, in their original order.
+    fn chosen(self) -> Vec<Action> {
+        self.actions
+            .into_iter()
+            .zip(self.chosen)
+            .filter_map(|(action, chosen)| chosen.then_some(action))
+            .collect()
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        frame.render_widget(Paragraph::new(self.header()), header);
+        let items: Vec<_> = self.rows.iter().map(|row| self.item(row)).collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(format!(" {} ", self.site)))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, body, &mut self.list);
+        frame.render_widget(Paragraph::new(self.footer()), footer);
+    }
+
+    fn header(&self) -> Line<'static> {
+        if self.reviewing {
+            let chosen = self.chosen.iter().filter(|chosen| **chosen).count();
+            return Line::from(format!(
+                "{} of {} action(s) chosen",
+                chosen,
+                self.actions.len()
+            ));
+        }
+        let count = |f: fn(&Status) -> bool| self.status.iter().filter(|s| f(s)).count();
+        let done = count(|s| matches!(s, Status::Done(_)));
+        let failed = count(|s| matches!(s, Status::Failed(_)));
+        Line::from(format!(
+            "{} of {} action(s) done, {} failed",
+            done,
+            self.actions.len(),
+            failed
+        ))
+    }
+
+    fn footer(&self) -> Line<'static> {
+        match &self.result {
+            Some(Ok(message)) => Line::styled(
+                format!("{} — press any key to exit", message),
+                Style::new().fg(Color::Green),
+            ),
+            Some(Err(message)) => Line::styled(
+                format!("{} — press any key to exit", message),
+                Style::new().fg(Color::Red),
+            ),
+            None if self.reviewing => {
+                Line::from("↑↓ move  space toggle  a toggle all  enter deploy  q cancel".to_owned())
+            }
+            None if self.paused => Line::styled(
+                "Paused after the current request — ↑↓ move  p resume  q cancel".to_owned(),
+                Style::new().fg(Color::Yellow),
+            ),
+            None => Line::from("↑↓ move  p pause  q cancel".to_owned()),
+        }
+    }
+
+    fn item(&self, row: &Row) -> ListItem<'static> {
+        match row {
+            Row::Dir { path, depth } => {
+                let below = self.below(path);
+                let chosen = below.iter().filter(|&&index| self.chosen[index]).count();
+                let check = match chosen {
+                    0 => "[ ] ",
+                    n if n == below.len() => "[x] ",
+                    _ => "[-] ",
+                };
+                let name = path.rsplit('/').next().unwrap_or(path);
+                ListItem::new(Line::from(vec![
+                    Span::raw("  ".repeat(*depth)),
+                    Span::raw(if self.reviewing { check } else { "" }),
+                    Span::styled(
+                        format!("{}/", name),
+                        Style::new().fg(Color::Blue).add_modifier(Modifier::BOLD),
+                    ),
+                ]))
+            }
+            Row::Action { index, depth } => {
+                let action = &self.actions[*index];
+                let path = &action.entry().path;
+                let name = path.rsplit('/').next().unwrap_or(path);
+                let color = match action.kind() {
+                    "upload" => Color::Green,
+                    _ => Color::Red,
+                };
+                let check = match self.chosen[*index] {
+                    true => "[x] ",
+                    false => "[ ] ",
+                };
+                let mut spans = vec![
+                    Span::raw("  ".repeat(*depth)),
+                    Span::raw(if self.reviewing { check } else { "" }),
+                    Span::styled(format!("{} ", action.kind()), Style::new().fg(color)),
+                    Span::raw(name.to_owned()),
+                ];
+                if action.bytes() > 0 {
+                    spans.push(Span::raw(format!("  {}", ByteSize(action.bytes()))));
+                }
+                spans.push(match &self.status[*index] {
+                    Status::Pending => Span::raw(""),
+                    Status::Running => Span::styled("  …", Style::new().fg(Color::Yellow)),
+                    Status::Done(duration) => {
+                        Span::styled(format!("  ✔ {:?}", duration), Style::new().fg(Color::Green))
+                    }
+                    Status::Failed(error) => {
+                        Span::styled(format!("  ✘ {}", error), Style::new().fg(Color::Red))
+                    }
+                });
+                ListItem::new(Line::from(spans))
+            }
+        }
+    }
+}
+
+/// Lay out actions as a tree: sorted by path, with a row for each directory before the actions
+/// below it.
+fn rows(actions: &[Action]) -> Vec<Row> {
+    let mut order: Vec<usize> = (0..actions.len()).collect();
+    order.sort_by(|&a, &b| actions[a].entry().path.cmp(&actions[b].entry().path));
+    let mut rows = Vec::new();
+    let mut open: Vec<&str> = Vec::new();
+    for index in order {
+        let components: Vec<&str> = actions[index].entry().path.split('/').collect();
+        let dirs = &components[..components.len() - 1];
+        let common = open.iter().zip(dirs).take_while(|(a, b)| a == b).count();
+        open.truncate(common);
+        for depth in common..dirs.len() {
+            open.push(dirs[depth]);
+            rows.push(Row::Dir {
+                path: dirs[..=depth].join("/"),
+                depth,
+            });
+        }
+        rows.push(Row::Action {
+            index,
+            depth: dirs.len(),
+        });
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trees::Entry;
+
+    fn upload(path: &str) -> Action {
+        Action::Upload(Entry {
+            path: path.to_owned(),
+            info: None,
+            local_path: None,
+            content: None,
+        })
+    }
+
+    #[test]
+    fn test_rows() {
+        let actions = [
+            upload("b/c/d.html"),
+            upload("a.css"),
+            upload("b/e.html"),
+            upload("b/c/f.html"),
+        ];
+        assert_eq!(
+            rows(&actions),
+            [
+                Row::Action { index: 1, depth: 0 },
+                Row::Dir {
+                    path: "b".to_owned(),
+                    depth: 0
+                },
+                Row::Dir {
+                    path: "b/c".to_owned(),
+                    depth: 1
+                },
+                Row::Action { index: 0, depth: 2 },
+                Row::Action { index: 3, depth: 2 },
+                Row::Action { index: 2, depth: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_toggle() {
+        let actions = vec![upload("a.css"), upload("b/c.html"), upload("b/d.html")];
+        let mut view = View::new("lorem.com", actions, true);
+        // Rows: a.css, b/, c.html, d.html.
+        view.list.select(Some(2));
+        view.toggle();
+        view.list.select(Some(1));
+        view.toggle();
+        assert_eq!(view.chosen, [true, true, true]);
+        view.toggle();
+        assert_eq!(view.chosen, [true, false, false]);
+        let chosen = view.chosen();
+        assert_eq!(chosen, [upload("a.css")]);
+    }
+}
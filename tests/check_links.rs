@@ -0,0 +1,34 @@
+use assert_cmd::prelude::*;
+use predicates::str::contains;
+use std::fs;
+
+mod common;
+
+#[test]
+fn test_check_links() {
+    let site = tempfile::tempdir().unwrap();
+    fs::create_dir(site.path().join("blog")).unwrap();
+    fs::write(
+        site.path().join("index.html"),
+        r#"<a href="blog/">Blog</a> <a href="https://example.com/">Elsewhere</a>"#,
+    )
+    .unwrap();
+    fs::write(
+        site.path().join("blog/index.html"),
+        r#"<a href="/">Home</a> <img src="../cat.png">"#,
+    )
+    .unwrap();
+    let config = common::config_file("username:password", site.path());
+
+    let mut cmd = common::command();
+    cmd.arg("check-links").arg("--config").arg(config.path());
+    cmd.assert()
+        .failure()
+        .stdout(contains("blog/index.html: ../cat.png\n"))
+        .stderr(contains("1 broken link(s) found"));
+
+    fs::write(site.path().join("cat.png"), "cat").unwrap();
+    let mut cmd = common::command();
+    cmd.arg("check-links").arg("--config").arg(config.path());
+    cmd.assert().success().stdout(contains("All links resolve"));
+}
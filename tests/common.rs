@@ -1,5 +1,38 @@
-use std::{collections::HashMap, io::Write, path::Path};
-use tempfile::NamedTempFile;
+// Each test binary uses only some of the helpers.
+#![allow(dead_code)]
+
+use assert_cmd::prelude::*;
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use tempfile::{NamedTempFile, TempDir};
+
+thread_local! {
+    /// Data and cache directories of the test running on the current thread, removed when it
+    /// ends, so that tests never see each other’s state nor touch the user’s.
+    static STATE: TempDir = tempfile::tempdir().unwrap();
+}
+
+/// Command running the program, with the data and cache directories of the current test.
+pub fn command() -> Command {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.env("XDG_DATA_HOME", data_dir())
+        .env("XDG_CACHE_HOME", cache_dir());
+    cmd
+}
+
+/// Data directory of the current test, as seen by [`command`].
+pub fn data_dir() -> PathBuf {
+    STATE.with(|dir| dir.path().join("data"))
+}
+
+/// Cache directory of the current test, as seen by [`command`].
+pub fn cache_dir() -> PathBuf {
+    STATE.with(|dir| dir.path().join("cache"))
+}
 
 pub fn config_file(auth: &str, path: impl AsRef<Path>) -> NamedTempFile {
     let mut file = NamedTempFile::new().unwrap();
@@ -0,0 +1,39 @@
+use assert_cmd::prelude::*;
+use predicates::str::contains;
+use std::io::Write;
+
+mod common;
+
+#[test]
+fn test_completions() {
+    let mut cmd = common::command();
+    let config = common::config_file("username:password", "/path/to/lorem");
+
+    cmd.arg("completions")
+        .arg("bash")
+        .arg("--config")
+        .arg(config.path());
+    cmd.assert()
+        .success()
+        .stdout(contains("_neocities-deploy()"))
+        .stdout(contains("$(neocities-deploy __complete sites 2>/dev/null)"));
+}
+
+#[test]
+fn test_complete_names() {
+    let config = common::config_file("username:password", "/path/to/lorem");
+    writeln!(config.as_file(), "[group.all]\nsites = [\"lorem.com\"]").unwrap();
+
+    for (names, expected) in [("sites", "lorem.com\n"), ("groups", "all\n")] {
+        let mut cmd = common::command();
+        cmd.arg("--config")
+            .arg(config.path())
+            .args(["__complete", names]);
+        cmd.assert().success().stdout(expected);
+    }
+
+    // Nothing is completed without a config file.
+    let mut cmd = common::command();
+    cmd.args(["--config", "/nonexistent.toml", "__complete", "sites"]);
+    cmd.assert().success().stdout("");
+}
@@ -0,0 +1,69 @@
+use assert_cmd::prelude::*;
+use mockito::Server;
+use predicates::str::contains;
+use serial_test::serial;
+use std::{env, fs};
+
+mod common;
+
+#[test]
+#[serial]
+fn test_diff() {
+    let mut server = Server::new();
+
+    let mock = server
+        .mock("GET", "/index.html")
+        .with_status(200)
+        .with_body("<html>\n<p>Hello</p>\n</html>\n")
+        .create();
+
+    env::set_var("NEOCITIES_DEPLOY_SITE_URL", server.url());
+
+    let site = tempfile::tempdir().unwrap();
+    fs::write(
+        site.path().join("index.html"),
+        "<html>\n<p>Hello, world!</p>\n</html>\n",
+    )
+    .unwrap();
+
+    let mut cmd = common::command();
+    let config = common::config_file("username:password", site.path());
+
+    cmd.arg("diff").arg("/index.html");
+    cmd.arg("--config").arg(config.path());
+    cmd.assert().success().stdout(
+        "--- remote/index.html\n\
+         +++ local/index.html\n\
+         @@ -1,3 +1,3 @@\n \
+         <html>\n\
+         -<p>Hello</p>\n\
+         +<p>Hello, world!</p>\n \
+         </html>\n",
+    );
+
+    env::remove_var("NEOCITIES_DEPLOY_SITE_URL");
+    mock.assert();
+}
+
+#[test]
+#[serial]
+fn test_diff_missing() {
+    let mut server = Server::new();
+
+    let mock = server.mock("GET", "/nope.html").with_status(404).create();
+
+    env::set_var("NEOCITIES_DEPLOY_SITE_URL", server.url());
+
+    let site = tempfile::tempdir().unwrap();
+    let mut cmd = common::command();
+    let config = common::config_file("username:password", site.path());
+
+    cmd.arg("diff").arg("nope.html");
+    cmd.arg("--config").arg(config.path());
+    cmd.assert().failure().stderr(contains(
+        "nope.html exists neither locally nor on site lorem.com",
+    ));
+
+    env::remove_var("NEOCITIES_DEPLOY_SITE_URL");
+    mock.assert();
+}
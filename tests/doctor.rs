@@ -0,0 +1,83 @@
+use assert_cmd::prelude::*;
+use mockito::Server;
+use predicates::str::contains;
+use serial_test::serial;
+use std::env;
+
+mod common;
+
+#[test]
+#[serial]
+fn test_doctor_ok() {
+    let mut server = Server::new();
+
+    let mock = server
+        .mock("GET", "/info")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            r#"{
+                "result": "success",
+                "info": {
+                    "sitename": "youpi",
+                    "views": 1,
+                    "hits": 1,
+                    "created_at": "Sat, 29 Jun 2013 10:11:38 -0000",
+                    "last_updated": null,
+                    "domain": null,
+                    "tags": [],
+                    "latest_ipfs_hash": null
+                }
+            }"#,
+        )
+        .create();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+
+    let site = tempfile::tempdir().unwrap();
+    let mut cmd = common::command();
+    let config = common::config_file("c6275ca833ac06c83926ccb00dff4c82", site.path());
+
+    cmd.arg("doctor").arg("--config").arg(config.path());
+    cmd.assert()
+        .success()
+        .stdout(contains("✔ Configuration file is valid"))
+        .stdout(contains("✔ Credentials are valid for site youpi"))
+        .stdout(contains("No problems found"));
+
+    mock.assert();
+}
+
+#[test]
+#[serial]
+fn test_doctor_problems() {
+    let mut server = Server::new();
+
+    let mock = server
+        .mock("GET", "/info")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            r#"{
+                "result": "error",
+                "error_type": "invalid_auth",
+                "message": "invalid credentials - please check your username and password"
+            }"#,
+        )
+        .create();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+
+    let mut cmd = common::command();
+    let config = common::config_file("username:password", "/path/to/lorem");
+
+    cmd.arg("doctor").arg("--config").arg(config.path());
+    cmd.assert()
+        .failure()
+        .stdout(contains("✘ Local path \"/path/to/lorem\" does not exist"))
+        .stdout(contains("✘ Authentication failed"))
+        .stdout(contains("! Password is stored in the configuration file"))
+        .stderr(contains("2 problem(s) found"));
+
+    mock.assert();
+}
@@ -0,0 +1,30 @@
+use assert_cmd::prelude::*;
+use predicates::str::contains;
+
+mod common;
+
+#[test]
+fn test_first_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+
+    let mut cmd = common::command();
+    cmd.args(["list", "--config"]).arg(&config);
+    cmd.assert()
+        .failure()
+        .stderr(contains("No configuration file found at"))
+        .stderr(contains(
+            "Run `neocities-deploy config` to configure a site",
+        ));
+
+    // Without `--config`, a project configuration file can be created instead.
+    let mut cmd = common::command();
+    cmd.current_dir(dir.path())
+        .env("HOME", dir.path())
+        .env("XDG_CONFIG_HOME", dir.path())
+        .env_remove("NEOCITIES_DEPLOY_PROFILE")
+        .arg("deploy");
+    cmd.assert()
+        .failure()
+        .stderr(contains("`neocities-deploy init`"));
+}
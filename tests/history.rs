@@ -0,0 +1,55 @@
+use assert_cmd::prelude::*;
+use mockito::Server;
+use predicates::str::{contains, is_match};
+use serial_test::serial;
+use std::{env, fs};
+
+mod common;
+
+#[test]
+#[serial]
+fn test_history() {
+    let mut server = Server::new();
+    let data = tempfile::tempdir().unwrap();
+
+    let list = server
+        .mock("GET", "/list")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(r#"{ "result": "success", "files": [] }"#)
+        .create();
+    let upload = server
+        .mock("POST", "/upload")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(r#"{ "result": "success", "message": "your file(s) have been uploaded" }"#)
+        .create();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+
+    let site = tempfile::tempdir().unwrap();
+    fs::write(site.path().join("index.html"), "<html></html>").unwrap();
+    let config = common::config_file("username:password", site.path());
+
+    let mut cmd = common::command();
+    cmd.env("XDG_DATA_HOME", data.path());
+    cmd.arg("deploy").arg("-m").arg("First version");
+    cmd.arg("--config").arg(config.path());
+    cmd.assert().success();
+
+    list.assert();
+    upload.assert();
+
+    let mut cmd = common::command();
+    cmd.env("XDG_DATA_HOME", data.path());
+    cmd.arg("history").arg("--actions");
+    cmd.arg("--config").arg(config.path());
+    cmd.assert()
+        .success()
+        .stdout(contains("Deploy history of site lorem.com\n"))
+        .stdout(
+            is_match(r"Z  success    1 uploaded \(13 B\), 0 deleted, 0 failed  First version\n")
+                .unwrap(),
+        )
+        .stdout(contains("    upload index.html\n"));
+}
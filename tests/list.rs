@@ -2,11 +2,13 @@ use assert_cmd::prelude::*;
 use indoc::indoc;
 use mockito::Server;
 use predicates::str::{contains, starts_with};
-use std::{env, process::Command};
+use serial_test::serial;
+use std::{env, fs};
 
 mod common;
 
 #[test]
+#[serial]
 fn test_list() {
     let mut server = Server::new();
 
@@ -47,7 +49,7 @@ fn test_list() {
 
     env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
 
-    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    let mut cmd = common::command();
     let config = common::config_file("username:password", "/path/to/lorem");
 
     cmd.arg("-v").arg("list").arg("--config").arg(config.path());
@@ -61,3 +63,296 @@ fn test_list() {
 
     mock.assert();
 }
+
+#[test]
+#[serial]
+fn test_list_tree_sorted() {
+    let mut server = Server::new();
+
+    let mock = server
+        .mock("GET", "/list")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(indoc! {r#"{
+            "result": "success",
+            "files": [{
+                "path": "index.html",
+                "is_directory": false,
+                "size": 1023,
+                "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000",
+                "sha1_hash": "c8aac06f343c962a24a7eb111aad739ff48b7fb1"
+            }, {
+                "path": "not_found.html",
+                "is_directory": false,
+                "size": 271,
+                "updated_at": "Sun, 14 Feb 2016 03:04:00 -0000",
+                "sha1_hash": "cfdf0bda2557c322be78302da23c32fec72ffc0b"
+            }, {
+                "path": "images",
+                "is_directory": true,
+                "updated_at": "Fri, 12 Feb 2016 03:04:00 -0000"
+            }, {
+                "path": "images/cat.png",
+                "is_directory": false,
+                "size": 16793,
+                "updated_at": "Fri, 12 Feb 2016 03:04:00 -0000",
+                "sha1_hash": "41fe08fc0dd44e79f799d03ece903e62be25dc7d"
+            }]
+        }"#})
+        .expect(2)
+        .create();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+    let config = common::config_file("username:password", "/path/to/lorem");
+
+    let mut cmd = common::command();
+    cmd.args(["list", "--tree", "--sort", "size", "--reverse", "--config"])
+        .arg(config.path());
+    cmd.assert().success().stdout(contains(concat!(
+        "            images/\n",
+        "   16.8 KB    cat.png\n",
+        "    1.0 KB  index.html\n",
+        "     271 B  not_found.html\n",
+    )));
+
+    let mut cmd = common::command();
+    cmd.args(["list", "--long", "--sort", "date", "--config"])
+        .arg(config.path());
+    cmd.assert().success().stdout(contains(concat!(
+        "            2016-02-12 03:04                                            images/\n",
+        "   16.8 KB  2016-02-12 03:04  41fe08fc0dd44e79f799d03ece903e62be25dc7d  images/cat.png\n",
+        "    1.0 KB  2016-02-13 03:04  c8aac06f343c962a24a7eb111aad739ff48b7fb1  index.html\n",
+        "     271 B  2016-02-14 03:04  cfdf0bda2557c322be78302da23c32fec72ffc0b  not_found.html\n",
+    )));
+
+    // The list is cached by the first command, unless `--refresh` is given.
+    let mut cmd = common::command();
+    cmd.args(["list", "--refresh", "--config"])
+        .arg(config.path());
+    cmd.assert().success().stdout(contains("not_found.html"));
+
+    mock.assert();
+}
+
+#[test]
+#[serial]
+fn test_list_filter_depth() {
+    let mut server = Server::new();
+
+    let mock = server
+        .mock("GET", "/list")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(indoc! {r#"{
+            "result": "success",
+            "files": [{
+                "path": "index.html",
+                "is_directory": false,
+                "size": 1023,
+                "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000",
+                "sha1_hash": "c8aac06f343c962a24a7eb111aad739ff48b7fb1"
+            }, {
+                "path": "not_found.html",
+                "is_directory": false,
+                "size": 271,
+                "updated_at": "Sun, 14 Feb 2016 03:04:00 -0000",
+                "sha1_hash": "cfdf0bda2557c322be78302da23c32fec72ffc0b"
+            }, {
+                "path": "images",
+                "is_directory": true,
+                "updated_at": "Fri, 12 Feb 2016 03:04:00 -0000"
+            }, {
+                "path": "images/cat.png",
+                "is_directory": false,
+                "size": 16793,
+                "updated_at": "Fri, 12 Feb 2016 03:04:00 -0000",
+                "sha1_hash": "41fe08fc0dd44e79f799d03ece903e62be25dc7d"
+            }]
+        }"#})
+        .expect(1)
+        .create();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+    let config = common::config_file("username:password", "/path/to/lorem");
+
+    let mut cmd = common::command();
+    cmd.args(["list", "--filter", "*.png", "--config"])
+        .arg(config.path());
+    cmd.assert()
+        .success()
+        .stdout("Listing site lorem.com\n   16.8 KB  images/cat.png\n");
+
+    let mut cmd = common::command();
+    cmd.args(["list", "--filter", "*.png", "--tree", "--config"])
+        .arg(config.path());
+    cmd.assert()
+        .success()
+        .stdout("Listing site lorem.com\n            images/\n   16.8 KB    cat.png\n");
+
+    let mut cmd = common::command();
+    cmd.args(["list", "--depth", "1", "--config"])
+        .arg(config.path());
+    cmd.assert().success().stdout(concat!(
+        "Listing site lorem.com\n",
+        "            images/\n",
+        "    1.0 KB  index.html\n",
+        "     271 B  not_found.html\n",
+    ));
+
+    mock.assert();
+}
+
+#[test]
+#[serial]
+fn test_list_local_and_one_side_only() {
+    let mut server = Server::new();
+
+    let mock = server
+        .mock("GET", "/list")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(indoc! {r#"{
+            "result": "success",
+            "files": [{
+                "path": "index.html",
+                "is_directory": false,
+                "size": 13,
+                "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000",
+                "sha1_hash": "941efb7368e46b27b937d34b07fc4d41da01b002"
+            }, {
+                "path": "old.html",
+                "is_directory": false,
+                "size": 271,
+                "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000",
+                "sha1_hash": "cfdf0bda2557c322be78302da23c32fec72ffc0b"
+            }]
+        }"#})
+        .expect(1)
+        .create();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+    let site = tempfile::tempdir().unwrap();
+    fs::write(site.path().join("index.html"), "<html></html>").unwrap();
+    fs::write(site.path().join("new.html"), "<html>New</html>").unwrap();
+    let config = common::config_file("username:password", site.path());
+
+    let mut cmd = common::command();
+    cmd.args(["list", "--local", "--config"]).arg(config.path());
+    cmd.assert().success().stdout(concat!(
+        "Listing local files of site lorem.com\n",
+        "      13 B  index.html\n",
+        "      16 B  new.html\n",
+    ));
+
+    let mut cmd = common::command();
+    cmd.args(["list", "--local-only", "--config"])
+        .arg(config.path());
+    cmd.assert()
+        .success()
+        .stdout("Listing site lorem.com\n      16 B  new.html\n");
+
+    let mut cmd = common::command();
+    cmd.args(["list", "--remote-only", "--config"])
+        .arg(config.path());
+    cmd.assert()
+        .success()
+        .stdout("Listing site lorem.com\n     271 B  old.html\n");
+
+    mock.assert();
+}
+
+#[test]
+#[serial]
+fn test_list_ndjson() {
+    let mut server = Server::new();
+
+    let mock = server
+        .mock("GET", "/list")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(indoc! {r#"{
+            "result": "success",
+            "files": [{
+                "path": "index.html",
+                "is_directory": false,
+                "size": 1023,
+                "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000",
+                "sha1_hash": "c8aac06f343c962a24a7eb111aad739ff48b7fb1"
+            }, {
+                "path": "images",
+                "is_directory": true,
+                "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000"
+            }]
+        }"#})
+        .create();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+
+    let mut cmd = common::command();
+    let config = common::config_file("username:password", "/path/to/lorem");
+
+    cmd.arg("list").arg("--format").arg("ndjson");
+    cmd.arg("--config").arg(config.path());
+    let output = cmd.assert().success().get_output().stdout.clone();
+
+    mock.assert();
+
+    let records: Vec<serde_json::Value> = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["path"], "images");
+    assert_eq!(records[0]["is_directory"], true);
+    assert_eq!(records[0]["size"], serde_json::Value::Null);
+    assert_eq!(records[1]["path"], "index.html");
+    assert_eq!(records[1]["size"], 1023);
+    assert_eq!(
+        records[1]["sha1_hash"],
+        "c8aac06f343c962a24a7eb111aad739ff48b7fb1"
+    );
+    assert_eq!(records[1]["site"], "lorem.com");
+}
+
+#[test]
+#[serial]
+fn test_list_color() {
+    let mut server = Server::new();
+
+    let mock = server
+        .mock("GET", "/list")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(indoc! {r#"{
+            "result": "success",
+            "files": [{
+                "path": "images",
+                "is_directory": true,
+                "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000"
+            }]
+        }"#})
+        .expect(1)
+        .create();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+
+    let config = common::config_file("username:password", "/path/to/lorem");
+
+    let mut cmd = common::command();
+    cmd.env("NO_COLOR", "1")
+        .args(["list", "--color", "always", "--config"])
+        .arg(config.path());
+    cmd.assert()
+        .success()
+        .stdout(contains("\x1b[1;34mimages/\x1b[0m"));
+
+    // Colors are only used on terminals by default.
+    let mut cmd = common::command();
+    cmd.args(["list", "--config"]).arg(config.path());
+    cmd.assert()
+        .success()
+        .stdout(contains("         images/\n"));
+
+    mock.assert();
+}
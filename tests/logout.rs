@@ -0,0 +1,53 @@
+use assert_cmd::prelude::*;
+use predicates::str::contains;
+use std::fs;
+
+mod common;
+
+#[test]
+fn test_logout() {
+    let config = common::config_file("c6275ca833ac06c83926ccb00dff4c82", "/path/to/lorem");
+
+    let mut cmd = common::command();
+    cmd.args(["logout", "lorem.com", "--config"])
+        .arg(config.path());
+    cmd.assert()
+        .success()
+        .stderr(contains("Credentials of site lorem.com removed"))
+        .stderr(contains("To revoke the API key"));
+    let contents = fs::read_to_string(config.path()).unwrap();
+    assert!(!contents.contains("c6275ca833ac06c83926ccb00dff4c82"));
+    assert!(contents.contains("lorem.com"));
+
+    let mut cmd = common::command();
+    cmd.args(["logout", "lorem.com", "--config"])
+        .arg(config.path());
+    cmd.assert()
+        .success()
+        .stderr(contains("Site lorem.com has no stored credentials"));
+
+    let mut cmd = common::command();
+    cmd.args(["logout", "lorem.com", "--remove-site", "--config"])
+        .arg(config.path());
+    cmd.assert()
+        .success()
+        .stderr(contains("Site lorem.com removed"));
+    assert!(!fs::read_to_string(config.path())
+        .unwrap()
+        .contains("lorem.com"));
+}
+
+#[test]
+fn test_logout_unknown_site() {
+    let config = common::config_file("username:password", "/path/to/lorem");
+
+    let mut cmd = common::command();
+    cmd.args(["logout", "ipsum.com", "--config"])
+        .arg(config.path());
+    cmd.assert()
+        .failure()
+        .stderr(contains("Site not found: ipsum.com"));
+    assert!(fs::read_to_string(config.path())
+        .unwrap()
+        .contains("username:password"));
+}
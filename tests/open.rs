@@ -0,0 +1,77 @@
+use assert_cmd::prelude::*;
+use mockito::Server;
+use serial_test::serial;
+use std::env;
+
+mod common;
+
+fn info(domain: &str) -> String {
+    format!(
+        r#"{{
+            "result": "success",
+            "info": {{
+                "sitename": "youpi",
+                "views": 1,
+                "hits": 1,
+                "created_at": "Sat, 29 Jun 2013 10:11:38 -0000",
+                "last_updated": null,
+                "domain": {},
+                "tags": [],
+                "latest_ipfs_hash": null
+            }}
+        }}"#,
+        domain
+    )
+}
+
+#[test]
+#[serial]
+fn test_open_print() {
+    let mut server = Server::new();
+
+    let mock = server
+        .mock("GET", "/info")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(info("null"))
+        .create();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+
+    let mut cmd = common::command();
+    let config = common::config_file("username:password", "/path/to/lorem");
+
+    cmd.arg("open").arg("/blog/").arg("--print");
+    cmd.arg("--config").arg(config.path());
+    cmd.assert()
+        .success()
+        .stdout("https://youpi.neocities.org/blog/\n");
+
+    mock.assert();
+}
+
+#[test]
+#[serial]
+fn test_open_print_domain() {
+    let mut server = Server::new();
+
+    let mock = server
+        .mock("GET", "/info")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(info(r#""youpi.example""#))
+        .create();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+
+    let mut cmd = common::command();
+    let config = common::config_file("username:password", "/path/to/lorem");
+
+    cmd.arg("open")
+        .arg("--print")
+        .arg("--config")
+        .arg(config.path());
+    cmd.assert().success().stdout("https://youpi.example/\n");
+
+    mock.assert();
+}
@@ -0,0 +1,158 @@
+use assert_cmd::prelude::*;
+use mockito::{Matcher, Server};
+use serial_test::serial;
+use std::{env, fs, io::Write};
+
+mod common;
+
+#[test]
+#[serial]
+fn test_plan_and_apply() {
+    let mut server = Server::new();
+
+    let list = server
+        .mock("GET", "/list")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(r#"{ "result": "success", "files": [] }"#)
+        .expect(1)
+        .create();
+    let upload = server
+        .mock("POST", "/upload")
+        .match_body(Matcher::Regex("name=\"index.html\"".to_owned()))
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(r#"{ "result": "success", "message": "your file(s) have been uploaded" }"#)
+        .expect(1)
+        .create();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+
+    let site = tempfile::tempdir().unwrap();
+    fs::write(site.path().join("index.html"), "<html></html>").unwrap();
+    let config = common::config_file("username:password", site.path());
+    let plan = tempfile::NamedTempFile::new().unwrap();
+
+    let mut cmd = common::command();
+    cmd.arg("plan").arg("--output").arg(plan.path());
+    cmd.arg("--config").arg(config.path());
+    cmd.assert()
+        .success()
+        .stderr(predicates::str::contains("upload index.html"));
+
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(plan.path()).unwrap()).unwrap();
+    assert_eq!(json["version"], 1);
+    assert_eq!(
+        json["sites"]["lorem.com"][0]["upload"]["path"],
+        "index.html"
+    );
+    assert_eq!(
+        json["sites"]["lorem.com"][0]["upload"]["info"]["sha1_sum"],
+        "941efb7368e46b27b937d34b07fc4d41da01b002"
+    );
+
+    let mut cmd = common::command();
+    cmd.arg("apply").arg(plan.path());
+    cmd.arg("--config").arg(config.path());
+    cmd.assert().success();
+
+    list.assert();
+    upload.assert();
+}
+
+#[test]
+#[serial]
+fn test_apply_changed_file() {
+    let mut server = Server::new();
+
+    let list = server
+        .mock("GET", "/list")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(r#"{ "result": "success", "files": [] }"#)
+        .create();
+    let upload = server.mock("POST", "/upload").expect(0).create();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+
+    let site = tempfile::tempdir().unwrap();
+    fs::write(site.path().join("index.html"), "<html></html>").unwrap();
+    let config = common::config_file("username:password", site.path());
+    let plan = tempfile::NamedTempFile::new().unwrap();
+
+    let mut cmd = common::command();
+    cmd.arg("plan").arg("--output").arg(plan.path());
+    cmd.arg("--config").arg(config.path());
+    cmd.assert().success();
+
+    fs::write(site.path().join("index.html"), "<html>changed</html>").unwrap();
+
+    let mut cmd = common::command();
+    cmd.arg("apply").arg(plan.path());
+    cmd.arg("--config").arg(config.path());
+    cmd.assert().failure().stderr(predicates::str::contains(
+        "File index.html has changed since the plan was made",
+    ));
+
+    list.assert();
+    upload.assert();
+}
+
+#[test]
+#[serial]
+fn test_apply_fingerprinted() {
+    let mut server = Server::new();
+
+    let list = server
+        .mock("GET", "/list")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(r#"{ "result": "success", "files": [] }"#)
+        .create();
+    let upload_css = server
+        .mock("POST", "/upload")
+        .match_body(Matcher::Regex(
+            r#"name="style\.[0-9a-f]{8}\.css""#.to_owned(),
+        ))
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(r#"{ "result": "success", "message": "your file(s) have been uploaded" }"#)
+        .expect(1)
+        .create();
+    // The HTML file is uploaded with its reference to the fingerprinted style sheet, which is
+    // not saved in the plan file.
+    let upload = server
+        .mock("POST", "/upload")
+        .match_body(Matcher::Regex(
+            r#"<link href="style\.[0-9a-f]{8}\.css">"#.to_owned(),
+        ))
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(r#"{ "result": "success", "message": "your file(s) have been uploaded" }"#)
+        .expect(1)
+        .create();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+
+    let site = tempfile::tempdir().unwrap();
+    fs::write(site.path().join("index.html"), r#"<link href="style.css">"#).unwrap();
+    fs::write(site.path().join("style.css"), "body {}").unwrap();
+    let config = common::config_file("username:password", site.path());
+    writeln!(config.as_file(), r#"fingerprint = ["*.css"]"#).unwrap();
+    let plan = tempfile::NamedTempFile::new().unwrap();
+
+    let mut cmd = common::command();
+    cmd.arg("plan").arg("--output").arg(plan.path());
+    cmd.arg("--config").arg(config.path());
+    cmd.assert().success();
+
+    let mut cmd = common::command();
+    cmd.arg("apply").arg(plan.path());
+    cmd.arg("--config").arg(config.path());
+    cmd.assert().success();
+
+    list.assert();
+    upload_css.assert();
+    upload.assert();
+}
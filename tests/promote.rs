@@ -0,0 +1,85 @@
+use assert_cmd::prelude::*;
+use mockito::{Matcher, Server};
+use serial_test::serial;
+use std::{env, io::Write};
+
+mod common;
+
+const PRODUCTION_AUTH: &str = "Basic dXNlcm5hbWU6cGFzc3dvcmQ=";
+const STAGING_AUTH: &str = "Bearer staging_key";
+
+#[test]
+#[serial]
+fn test_promote() {
+    let mut server = Server::new();
+
+    let staging_list = server
+        .mock("GET", "/list")
+        .match_header("authorization", STAGING_AUTH)
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            r#"{ "result": "success", "files": [{
+                "path": "a.html",
+                "is_directory": false,
+                "size": 8,
+                "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000",
+                "sha1_hash": "aa64f8b818c3abd395e3a53ae0b5c07845c81adc"
+            }] }"#,
+        )
+        .create();
+    let production_list = server
+        .mock("GET", "/list")
+        .match_header("authorization", PRODUCTION_AUTH)
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            r#"{ "result": "success", "files": [{
+                "path": "old.html",
+                "is_directory": false,
+                "size": 10,
+                "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000",
+                "sha1_hash": "c8aac06f343c962a24a7eb111aad739ff48b7fb1"
+            }] }"#,
+        )
+        .create();
+    let download = server
+        .mock("GET", "/a.html")
+        .with_status(200)
+        .with_body("<p>a</p>")
+        .create();
+    let upload = server
+        .mock("POST", "/upload")
+        .match_header("authorization", PRODUCTION_AUTH)
+        .match_body(Matcher::Regex("<p>a</p>".to_owned()))
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(r#"{ "result": "success", "message": "your file(s) have been uploaded" }"#)
+        .create();
+    let delete = server
+        .mock("POST", "/delete")
+        .match_header("authorization", PRODUCTION_AUTH)
+        .match_body("filenames%5B%5D=old.html")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(r#"{ "result": "success", "message": "file(s) have been deleted" }"#)
+        .create();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+    env::set_var("NEOCITIES_DEPLOY_SITE_URL", server.url());
+
+    let config = common::config_file("username:password", "/path/to/lorem");
+    writeln!(config.as_file(), "staging = {{ auth = 'staging_key' }}").unwrap();
+
+    let mut cmd = common::command();
+    cmd.arg("promote").arg("--config").arg(config.path());
+    cmd.assert().success();
+
+    env::remove_var("NEOCITIES_DEPLOY_SITE_URL");
+
+    staging_list.assert();
+    production_list.assert();
+    download.assert();
+    upload.assert();
+    delete.assert();
+}
@@ -0,0 +1,74 @@
+use assert_cmd::prelude::*;
+use indoc::indoc;
+use mockito::Server;
+use predicates::str::{contains, starts_with};
+use std::{env, io::Write};
+
+mod common;
+
+#[test]
+fn test_report() {
+    let mut server = Server::new();
+
+    let mock = server
+        .mock("GET", "/list")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(indoc! {r#"{
+            "result": "success",
+            "files": [{
+                "path": "index.html",
+                "is_directory": false,
+                "size": 1023,
+                "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000",
+                "sha1_hash": "c8aac06f343c962a24a7eb111aad739ff48b7fb1"
+            }, {
+                "path": "images",
+                "is_directory": true,
+                "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000"
+            }, {
+                "path": "images/cats",
+                "is_directory": true,
+                "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000"
+            }, {
+                "path": "images/cats/cat.png",
+                "is_directory": false,
+                "size": 16793,
+                "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000",
+                "sha1_hash": "41fe08fc0dd44e79f799d03ece903e62be25dc7d"
+            }, {
+                "path": "images/dog.png",
+                "is_directory": false,
+                "size": 2000,
+                "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000",
+                "sha1_hash": "cfdf0bda2557c322be78302da23c32fec72ffc0b"
+            }]
+        }"#})
+        .create();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+
+    let mut cmd = common::command();
+    let config = common::config_file("username:password", "/path/to/lorem");
+    writeln!(config.as_file(), "free_account = true").unwrap();
+
+    cmd.arg("du")
+        .arg("--top")
+        .arg("2")
+        .arg("--config")
+        .arg(config.path());
+    cmd.assert()
+        .success()
+        .stdout(starts_with("Storage report for site lorem.com\n"))
+        .stdout(contains(
+            "Total: 19.8 KB in 3 file(s), 0.0% of the 1 GB quota\n",
+        ))
+        .stdout(contains(
+            "Directories:\n   18.8 KB  images/\n   16.8 KB  images/cats/\n",
+        ))
+        .stdout(contains(
+            "Largest files:\n   16.8 KB  images/cats/cat.png\n    2.0 KB  images/dog.png\n",
+        ));
+
+    mock.assert();
+}
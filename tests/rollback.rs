@@ -0,0 +1,93 @@
+use assert_cmd::prelude::*;
+use mockito::{Matcher, Server};
+use predicates::str::contains;
+use serial_test::serial;
+use std::{env, fs};
+
+mod common;
+
+#[test]
+#[serial]
+fn test_rollback() {
+    let mut server = Server::new();
+    let data = tempfile::tempdir().unwrap();
+
+    let list = server
+        .mock("GET", "/list")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            r#"{ "result": "success", "files": [{
+                "path": "index.html",
+                "is_directory": false,
+                "size": 9,
+                "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000",
+                "sha1_hash": "62e7dab0ae5d5e1a2b3c0c1e9c16a8c2c2a8e2a1"
+            }] }"#,
+        )
+        .expect(2)
+        .create();
+    let download = server
+        .mock("GET", "/index.html")
+        .with_status(200)
+        .with_body("<p>v1</p>")
+        .create();
+    let upload = server
+        .mock("POST", "/upload")
+        .match_body(Matcher::Regex("<p>v2</p>".to_owned()))
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(r#"{ "result": "success", "message": "your file(s) have been uploaded" }"#)
+        .create();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+    env::set_var("NEOCITIES_DEPLOY_SITE_URL", server.url());
+
+    let site = tempfile::tempdir().unwrap();
+    fs::write(site.path().join("index.html"), "<p>v2</p>").unwrap();
+    fs::write(site.path().join("new.html"), "<p>new</p>").unwrap();
+    let config = common::config_file("username:password", site.path());
+
+    let mut cmd = common::command();
+    cmd.env("XDG_DATA_HOME", data.path());
+    cmd.arg("deploy").arg("--stash");
+    cmd.arg("--config").arg(config.path());
+    cmd.assert()
+        .success()
+        .stderr(contains("Stashing 1 remote file(s) for rollback"));
+
+    let stash = data.path().join("neocities-deploy/rollback/lorem.com");
+    assert_eq!(
+        fs::read_to_string(stash.join("files/index.html")).unwrap(),
+        "<p>v1</p>"
+    );
+
+    let restore = server
+        .mock("POST", "/upload")
+        .match_body(Matcher::Regex("<p>v1</p>".to_owned()))
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(r#"{ "result": "success", "message": "your file(s) have been uploaded" }"#)
+        .create();
+    let delete = server
+        .mock("POST", "/delete")
+        .match_body("filenames%5B%5D=new.html")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(r#"{ "result": "success", "message": "file(s) have been deleted" }"#)
+        .create();
+
+    let mut cmd = common::command();
+    cmd.env("XDG_DATA_HOME", data.path());
+    cmd.arg("rollback").arg("--config").arg(config.path());
+    cmd.assert().success();
+
+    env::remove_var("NEOCITIES_DEPLOY_SITE_URL");
+
+    list.assert();
+    download.assert();
+    upload.assert();
+    restore.assert();
+    delete.assert();
+    assert!(!stash.exists());
+}
@@ -0,0 +1,71 @@
+use assert_cmd::prelude::*;
+use mockito::Server;
+use predicates::str::contains;
+use serial_test::serial;
+use std::env;
+
+mod common;
+
+fn list(hash: &str) -> String {
+    format!(
+        r#"{{
+            "result": "success",
+            "files": [{{
+                "path": "index.html",
+                "is_directory": false,
+                "size": 13,
+                "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000",
+                "sha1_hash": "{}"
+            }}]
+        }}"#,
+        hash
+    )
+}
+
+#[test]
+#[serial]
+fn test_snapshot() {
+    let mut server = Server::new();
+    let data = tempfile::tempdir().unwrap();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+    let config = common::config_file("username:password", "/path/to/lorem");
+    let snapshot = |action: &str| {
+        let mut cmd = common::command();
+        cmd.env("XDG_DATA_HOME", data.path());
+        cmd.arg("snapshot").arg(action);
+        cmd.arg("--config").arg(config.path());
+        cmd.assert()
+    };
+
+    snapshot("diff")
+        .failure()
+        .stderr(contains("No snapshot of site lorem.com"));
+
+    let mock = server
+        .mock("GET", "/list")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(list("c8aac06f343c962a24a7eb111aad739ff48b7fb1"))
+        .expect(2)
+        .create();
+    snapshot("save")
+        .success()
+        .stdout("Saved snapshot of site lorem.com (1 file(s))\n");
+    snapshot("diff").success();
+    mock.assert();
+    mock.remove();
+
+    // The file was changed with the web editor.
+    let mock = server
+        .mock("GET", "/list")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(list("943a702d06f34599aee1f8da8ef9f7296031d699"))
+        .create();
+    snapshot("diff")
+        .failure()
+        .stdout(contains("  modified  index.html\n"))
+        .stderr(contains("1 file(s) changed since the snapshot"));
+    mock.assert();
+}
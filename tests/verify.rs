@@ -0,0 +1,69 @@
+use assert_cmd::prelude::*;
+use indoc::indoc;
+use mockito::Server;
+use predicates::str::contains;
+use serial_test::serial;
+use std::{env, fs};
+
+mod common;
+
+const LIST: &str = indoc! {r#"{
+    "result": "success",
+    "files": [{
+        "path": "extra.html",
+        "is_directory": false,
+        "size": 10,
+        "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000",
+        "sha1_hash": "c8aac06f343c962a24a7eb111aad739ff48b7fb1"
+    }, {
+        "path": "hello.txt",
+        "is_directory": false,
+        "size": 13,
+        "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000",
+        "sha1_hash": "943a702d06f34599aee1f8da8ef9f7296031d699"
+    }, {
+        "path": "images",
+        "is_directory": true,
+        "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000"
+    }, {
+        "path": "images/cat.png",
+        "is_directory": false,
+        "size": 3,
+        "updated_at": "Sat, 13 Feb 2016 03:04:00 -0000",
+        "sha1_hash": "41fe08fc0dd44e79f799d03ece903e62be25dc7d"
+    }]
+}"#};
+
+#[test]
+#[serial]
+fn test_verify() {
+    let mut server = Server::new();
+
+    let mock = server
+        .mock("GET", "/list")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(LIST)
+        .create();
+
+    env::set_var("NEOCITIES_DEPLOY_API_URL", server.url());
+
+    let site = tempfile::tempdir().unwrap();
+    fs::write(site.path().join("hello.txt"), "Hello, world!").unwrap();
+    fs::write(site.path().join("index.html"), "<html></html>").unwrap();
+    fs::create_dir(site.path().join("images")).unwrap();
+    fs::write(site.path().join("images/cat.png"), "meow!").unwrap();
+
+    let mut cmd = common::command();
+    let config = common::config_file("username:password", site.path());
+
+    cmd.arg("verify").arg("--config").arg(config.path());
+    cmd.assert()
+        .failure()
+        .stdout(contains("     extra  extra.html\n"))
+        .stdout(contains(" truncated  images/cat.png\n"))
+        .stdout(contains("   missing  index.html\n"))
+        .stderr(contains("3 file(s) differ"));
+
+    mock.assert();
+}